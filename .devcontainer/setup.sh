@@ -17,8 +17,7 @@ sudo apt-get install -y \
     pkg-config \
     libssl-dev \
     libpq-dev \
-    wkhtmltopdf \
-    xvfb \
+    fonts-dejavu-core \
     r-base \
     r-base-dev
 
@@ -0,0 +1,593 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::elements::is_element;
+
+/// Coefficients may be halves, so totals are compared with a tolerance.
+const EPSILON: f64 = 1e-9;
+const STATES: &[&str] = &["s", "l", "g", "aq"];
+const FORWARD: char = '→';
+const REVERSIBLE: char = '⇌';
+/// Written arrows and their canonical form, longest first so `<=>` is not read as `=>`.
+const ARROWS: &[(&str, char)] = &[
+    ("\\rightleftharpoons", REVERSIBLE),
+    ("\\longrightarrow", FORWARD),
+    ("\\rightarrow", FORWARD),
+    ("\\to", FORWARD),
+    ("<-->", REVERSIBLE),
+    ("<=>", REVERSIBLE),
+    ("<->", REVERSIBLE),
+    ("⇄", REVERSIBLE),
+    ("↔", REVERSIBLE),
+    ("⟷", REVERSIBLE),
+    ("-->", FORWARD),
+    ("->", FORWARD),
+    ("=>", FORWARD),
+    ("⟶", FORWARD),
+    ("⇒", FORWARD),
+];
+const HYDRATE_DOTS: &[char] = &['·', '•', '.', '*'];
+
+/// The outcome of checking one equation found in a response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EquationCheck {
+    /// The equation as the student wrote it, with arrows normalised.
+    pub equation: String,
+    pub atoms_balanced: bool,
+    pub charge_balanced: bool,
+    /// Species written without (s), (l), (g) or (aq).
+    #[serde(default)]
+    pub missing_state_symbols: Vec<String>,
+    /// Everything wrong with the equation, e.g. `O: 2 on the left, 1 on the right`.
+    #[serde(default)]
+    pub issues: Vec<String>,
+}
+
+impl EquationCheck {
+    pub fn is_correct(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        if self.is_correct() {
+            "balanced, with state symbols".to_string()
+        } else {
+            self.issues.join("; ")
+        }
+    }
+}
+
+/// Atom counts by element symbol.
+pub(super) type Composition = BTreeMap<String, f64>;
+
+struct Species {
+    /// Formula and charge as written, without coefficient or state.
+    formula: String,
+    coefficient: f64,
+    atoms: Composition,
+    charge: f64,
+    state: Option<String>,
+    electron: bool,
+}
+
+/// Finds the equations in free text and checks each for atom balance, charge
+/// balance and state symbols. Equations may use `->`, `→`, `⇌`, `<=>` or mhchem
+/// `\ce{...}` notation, with charges written as `Fe3+`, `SO4^2-` or `Cu²⁺`.
+pub fn check_equations(text: &str) -> Vec<EquationCheck> {
+    let mut checks: Vec<EquationCheck> = Vec::new();
+
+    for line in normalise(text).lines() {
+        let arrows: Vec<(usize, char)> = line.char_indices().filter(|(_, c)| *c == FORWARD || *c == REVERSIBLE).collect();
+
+        for (i, &(at, arrow)) in arrows.iter().enumerate() {
+            // Chained equations share the species between arrows
+            let start = i.checked_sub(1).map(|j| arrows[j].0 + arrows[j].1.len_utf8()).unwrap_or(0);
+            let end = arrows.get(i + 1).map(|(pos, _)| *pos).unwrap_or(line.len());
+
+            let (Some((left_text, left)), Some((right_text, right))) =
+                (trailing_side(&line[start..at]), leading_side(&line[at + arrow.len_utf8()..end]))
+            else {
+                continue;
+            };
+            if !left.iter().chain(&right).any(looks_chemical) {
+                continue;
+            }
+
+            let equation = format!("{} {} {}", left_text, arrow, right_text);
+            if !checks.iter().any(|c| c.equation == equation) {
+                checks.push(check(equation, &left, &right));
+            }
+        }
+    }
+
+    checks
+}
+
+fn check(equation: String, left: &[Species], right: &[Species]) -> EquationCheck {
+    let mut issues = Vec::new();
+
+    let (left_atoms, right_atoms) = (total_atoms(left), total_atoms(right));
+    let elements: BTreeSet<&String> = left_atoms.keys().chain(right_atoms.keys()).collect();
+    for element in elements {
+        let on_left = left_atoms.get(element).copied().unwrap_or(0.0);
+        let on_right = right_atoms.get(element).copied().unwrap_or(0.0);
+        if (on_left - on_right).abs() > EPSILON {
+            issues.push(format!("{}: {} on the left, {} on the right", element, on_left, on_right));
+        }
+    }
+    let atoms_balanced = issues.is_empty();
+
+    let (left_charge, right_charge) = (total_charge(left), total_charge(right));
+    let charge_balanced = (left_charge - right_charge).abs() <= EPSILON;
+    if !charge_balanced {
+        issues.push(format!(
+            "Charge: {} on the left, {} on the right",
+            signed(left_charge),
+            signed(right_charge)
+        ));
+    }
+
+    // Electrons in half-equations take no state
+    let missing_state_symbols: Vec<String> = left
+        .iter()
+        .chain(right)
+        .filter(|s| s.state.is_none() && !s.electron)
+        .map(|s| s.formula.clone())
+        .collect();
+    if !missing_state_symbols.is_empty() {
+        issues.push(format!("No state symbol for {}", missing_state_symbols.join(", ")));
+    }
+
+    EquationCheck {
+        equation,
+        atoms_balanced,
+        charge_balanced,
+        missing_state_symbols,
+        issues,
+    }
+}
+
+fn total_atoms(side: &[Species]) -> Composition {
+    let mut atoms = Composition::new();
+    for species in side {
+        for (element, count) in &species.atoms {
+            *atoms.entry(element.clone()).or_default() += species.coefficient * count;
+        }
+    }
+    atoms
+}
+
+fn total_charge(side: &[Species]) -> f64 {
+    side.iter().map(|s| s.coefficient * s.charge).sum()
+}
+
+fn signed(charge: f64) -> String {
+    if charge > 0.0 {
+        format!("+{}", charge)
+    } else {
+        charge.to_string()
+    }
+}
+
+/// Filters out arrows between stray capital letters, such as `C → B` in prose.
+fn looks_chemical(species: &Species) -> bool {
+    species.state.is_some()
+        || species.charge != 0.0
+        || species.coefficient != 1.0
+        || species.atoms.values().sum::<f64>() > 1.0
+}
+
+/// Unwraps mhchem and maths markup and rewrites every arrow as `→` or `⇌`.
+fn normalise(text: &str) -> String {
+    let mut text = text.to_string();
+    while let Some(start) = text.find("\\ce{") {
+        let inner_start = start + "\\ce{".len();
+        let mut depth = 1;
+        let end = text[inner_start..].char_indices().find_map(|(i, c)| {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            (depth == 0).then_some(inner_start + i)
+        });
+        match end {
+            Some(end) => {
+                let inner = text[inner_start..end].to_string();
+                text.replace_range(start..=end, &inner);
+            }
+            None => text.replace_range(start..inner_start, ""),
+        }
+    }
+
+    // LaTeX subscripts: NO_2 and NO_{2} are NO2
+    let mut text = text.replace('$', " ").replace("_{", "_");
+    while let Some(start) = text.find('_') {
+        let digits_end = text[start + 1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map(|i| start + 1 + i)
+            .unwrap_or(text.len());
+        let closing = usize::from(text[digits_end..].starts_with('}'));
+        let digits = text[start + 1..digits_end].to_string();
+        text.replace_range(start..digits_end + closing, &digits);
+    }
+    for (written, arrow) in ARROWS {
+        text = text.replace(written, &format!(" {} ", arrow));
+    }
+    text
+}
+
+/// The longest run of species ending at the end of `context`.
+fn trailing_side(context: &str) -> Option<(String, Vec<Species>)> {
+    let tokens: Vec<&str> = context.split_whitespace().collect();
+    (0..tokens.len()).find_map(|k| {
+        let text = tokens[k..].join(" ");
+        first_side([text.as_str(), text.trim_start_matches(['(', '"', '\'', '“', '‘'])])
+    })
+}
+
+/// The longest run of species starting at the start of `context`.
+fn leading_side(context: &str) -> Option<(String, Vec<Species>)> {
+    let tokens: Vec<&str> = context.split_whitespace().collect();
+    (1..=tokens.len()).rev().find_map(|k| {
+        let text = tokens[..k].join(" ");
+        let without_punctuation = text.trim_end_matches(['.', ',', ';', ':', '!', '?', '"', '\'', '”', '’']);
+        first_side([text.as_str(), without_punctuation, without_punctuation.trim_end_matches(')')])
+    })
+}
+
+/// The first candidate, with or without surrounding punctuation, that parses as a side.
+fn first_side<const N: usize>(candidates: [&str; N]) -> Option<(String, Vec<Species>)> {
+    candidates
+        .into_iter()
+        .find_map(|candidate| parse_side(candidate).map(|side| (candidate.to_string(), side)))
+}
+
+fn parse_side(text: &str) -> Option<Vec<Species>> {
+    split_species(text).into_iter().map(parse_species).collect()
+}
+
+/// Splits a side on `+` separators, telling them apart from charges: `+` is a
+/// charge in `NH4+ + OH-` and `Fe3+(aq)`, and a separator in `H2+O2`.
+fn split_species(text: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    for (i, &(pos, c)) in chars.iter().enumerate() {
+        if c != '+' {
+            continue;
+        }
+        let previous = i.checked_sub(1).map(|j| chars[j].1);
+        let next = chars.get(i + 1).map(|(_, c)| *c);
+        let separator = match previous {
+            None => true,
+            Some(p) if p.is_whitespace() => true,
+            Some('^' | '{') => false,
+            // A charge is written before the state, never after it
+            Some(_) if split_state(text[start..pos].trim_end()).1.is_some() => true,
+            Some(_) => next.is_some_and(|n| n.is_ascii_uppercase() || n.is_ascii_digit() || n == '[' || n == '½'),
+        };
+        if separator {
+            chunks.push(&text[start..pos]);
+            start = pos + c.len_utf8();
+        }
+    }
+
+    chunks.push(&text[start..]);
+    chunks
+}
+
+fn parse_species(text: &str) -> Option<Species> {
+    let (coefficient, rest) = split_coefficient(text.trim())?;
+    let (rest, state) = split_state(rest.trim_start());
+    let written = rest.trim_end();
+    let (formula, charge) = split_charge(written)?;
+
+    let electron = formula == "e";
+    let atoms = if electron { Composition::new() } else { parse_formula(&formula)? };
+
+    Some(Species {
+        formula: written.to_string(),
+        coefficient,
+        atoms,
+        charge: if electron && charge == 0.0 { -1.0 } else { charge },
+        state,
+        electron,
+    })
+}
+
+/// Reads a leading coefficient such as `2`, `0.5`, `1/2` or `½`.
+fn split_coefficient(text: &str) -> Option<(f64, &str)> {
+    if let Some(rest) = text.strip_prefix('½') {
+        return Some((0.5, rest));
+    }
+
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '/'))
+        .unwrap_or(text.len());
+    if end == 0 {
+        return Some((1.0, text));
+    }
+
+    let number = &text[..end];
+    let value = match number.split_once('/') {
+        Some((numerator, denominator)) => numerator.parse::<f64>().ok()? / denominator.parse::<f64>().ok()?,
+        None => number.parse::<f64>().ok()?,
+    };
+    (value.is_finite() && value > 0.0).then_some((value, &text[end..]))
+}
+
+fn split_state(text: &str) -> (&str, Option<String>) {
+    if let Some(body) = text.strip_suffix(')') {
+        if let Some(open) = body.rfind('(') {
+            let state = &body[open + 1..];
+            if STATES.contains(&state) {
+                return (text[..open].trim_end(), Some(state.to_string()));
+            }
+        }
+    }
+    (text, None)
+}
+
+/// Separates a formula from its charge. Without a caret or superscripts,
+/// trailing digits are ambiguous: `Fe3+` is Fe³⁺ and `SO42-` is SO₄²⁻, but
+/// `NH4+` is NH₄⁺.
+fn split_charge(text: &str) -> Option<(String, f64)> {
+    if let Some(caret) = text.rfind('^') {
+        let charge = parse_charge(text[caret + 1..].trim_start_matches('{').trim_end_matches('}'))?;
+        return Some((text[..caret].to_string(), charge));
+    }
+
+    let superscript_start = text
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| from_superscript(*c).is_some())
+        .last()
+        .map(|(i, _)| i);
+    if let Some(start) = superscript_start {
+        let charge: String = text[start..].chars().filter_map(from_superscript).collect();
+        return Some((text[..start].to_string(), parse_charge(&charge)?));
+    }
+
+    let core = text.trim_end_matches(['+', '-', '−']);
+    let signs = &text[core.len()..];
+    if signs.is_empty() {
+        return Some((text.to_string(), 0.0));
+    }
+    let sign = sign_of(signs)?;
+    let count = signs.chars().count();
+    if count > 1 {
+        return Some((core.to_string(), sign * count as f64));
+    }
+
+    let stem = core.trim_end_matches(|c: char| c.is_ascii_digit());
+    let digits = &core[stem.len()..];
+    if digits.len() == 1 && is_element(stem) {
+        return Some((stem.to_string(), sign * digits.parse::<f64>().ok()?));
+    }
+    if digits.len() >= 2 && matches!(digits.as_bytes()[digits.len() - 1], b'2'..=b'4') {
+        let magnitude = (digits.as_bytes()[digits.len() - 1] - b'0') as f64;
+        return Some((core[..core.len() - 1].to_string(), sign * magnitude));
+    }
+    Some((core.to_string(), sign))
+}
+
+/// Parses `2+`, `+2`, `3-`, `+` or `--`.
+fn parse_charge(text: &str) -> Option<f64> {
+    let digits: String = text.chars().filter(|c| c.is_ascii_digit()).collect();
+    let signs: String = text.chars().filter(|c| !c.is_ascii_digit()).collect();
+    if signs.is_empty() {
+        return None;
+    }
+    let sign = sign_of(&signs)?;
+    let magnitude = if digits.is_empty() {
+        signs.chars().count() as f64
+    } else if signs.chars().count() == 1 {
+        digits.parse::<f64>().ok()?
+    } else {
+        return None;
+    };
+    Some(sign * magnitude)
+}
+
+fn sign_of(signs: &str) -> Option<f64> {
+    if signs.chars().all(|c| c == '+') {
+        Some(1.0)
+    } else if signs.chars().all(|c| c == '-' || c == '−') {
+        Some(-1.0)
+    } else {
+        None
+    }
+}
+
+fn from_superscript(c: char) -> Option<char> {
+    match c {
+        '⁰' => Some('0'),
+        '¹' => Some('1'),
+        '²' => Some('2'),
+        '³' => Some('3'),
+        '⁴'..='⁹' => char::from_digit(c as u32 - '⁴' as u32 + 4, 10),
+        '⁺' => Some('+'),
+        '⁻' => Some('-'),
+        _ => None,
+    }
+}
+
+/// Parses formulas such as `Ca(OH)2`, `[Cu(H2O)6]`, `C₂H₅OH` and `CuSO4·5H2O`.
+pub(super) fn parse_formula(text: &str) -> Option<Composition> {
+    let chars: Vec<char> = text
+        .chars()
+        .map(|c| match c {
+            '₀'..='₉' => char::from_digit(c as u32 - '₀' as u32, 10).unwrap_or(c),
+            _ => c,
+        })
+        .collect();
+    let mut pos = 0;
+    let mut atoms = Composition::new();
+    let mut multiplier = 1.0;
+
+    loop {
+        for (element, count) in parse_group(&chars, &mut pos)? {
+            *atoms.entry(element).or_default() += count * multiplier;
+        }
+        match chars.get(pos) {
+            None => break,
+            Some(c) if HYDRATE_DOTS.contains(c) => {
+                pos += 1;
+                multiplier = parse_count(&chars, &mut pos).unwrap_or(1.0);
+            }
+            Some(_) => return None,
+        }
+    }
+
+    Some(atoms)
+}
+
+fn parse_group(chars: &[char], pos: &mut usize) -> Option<Composition> {
+    let mut atoms = Composition::new();
+
+    while let Some(&c) = chars.get(*pos) {
+        let part = if c.is_ascii_uppercase() {
+            let mut symbol = c.to_string();
+            *pos += 1;
+            if let Some(&next) = chars.get(*pos).filter(|n| n.is_ascii_lowercase()) {
+                symbol.push(next);
+                *pos += 1;
+            }
+            if !is_element(&symbol) {
+                return None;
+            }
+            Composition::from([(symbol, 1.0)])
+        } else if c == '(' || c == '[' {
+            let close = if c == '(' { ')' } else { ']' };
+            *pos += 1;
+            let inner = parse_group(chars, pos)?;
+            if chars.get(*pos) != Some(&close) {
+                return None;
+            }
+            *pos += 1;
+            inner
+        } else {
+            break;
+        };
+
+        let count = parse_count(chars, pos).unwrap_or(1.0);
+        for (element, n) in part {
+            *atoms.entry(element).or_default() += n * count;
+        }
+    }
+
+    (!atoms.is_empty()).then_some(atoms)
+}
+
+fn parse_count(chars: &[char], pos: &mut usize) -> Option<f64> {
+    let start = *pos;
+    while chars.get(*pos).is_some_and(|c| c.is_ascii_digit()) {
+        *pos += 1;
+    }
+    chars[start..*pos].iter().collect::<String>().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn only(text: &str) -> EquationCheck {
+        let mut checks = check_equations(text);
+        assert_eq!(checks.len(), 1, "expected one equation in {:?}", text);
+        checks.remove(0)
+    }
+
+    #[test]
+    fn accepts_a_balanced_equation_with_states() {
+        let check = only("2H2(g) + O2(g) → 2H2O(l)");
+        assert!(check.is_correct(), "{:?}", check.issues);
+        assert_eq!(check.equation, "2H2(g) + O2(g) → 2H2O(l)");
+    }
+
+    #[test]
+    fn reports_unbalanced_atoms_and_missing_states() {
+        let check = only("H2 + O2 -> H2O");
+        assert!(!check.atoms_balanced);
+        assert!(check.charge_balanced);
+        assert_eq!(check.missing_state_symbols, ["H2", "O2", "H2O"]);
+        assert_eq!(
+            check.issues,
+            ["O: 2 on the left, 1 on the right", "No state symbol for H2, O2, H2O"]
+        );
+    }
+
+    #[test]
+    fn reads_trailing_digits_as_charge_only_when_they_must_be() {
+        assert_eq!(split_charge("SO42-"), Some(("SO4".to_string(), -2.0)));
+        assert_eq!(split_charge("NH4+"), Some(("NH4".to_string(), 1.0)));
+        assert_eq!(split_charge("S2-"), Some(("S".to_string(), -2.0)));
+        assert_eq!(split_charge("Fe3+"), Some(("Fe".to_string(), 3.0)));
+        assert_eq!(split_charge("OH-"), Some(("OH".to_string(), -1.0)));
+        assert_eq!(split_charge("SO4^2-"), Some(("SO4".to_string(), -2.0)));
+        assert_eq!(split_charge("Cu²⁺"), Some(("Cu".to_string(), 2.0)));
+        assert_eq!(split_charge("H2O"), Some(("H2O".to_string(), 0.0)));
+    }
+
+    #[test]
+    fn tells_separators_from_charges() {
+        assert_eq!(split_species("H2+O2"), ["H2", "O2"]);
+        assert_eq!(split_species("NH4+ + OH-"), ["NH4+ ", " OH-"]);
+        assert_eq!(split_species("Fe3+(aq)+3OH-(aq)"), ["Fe3+(aq)", "3OH-(aq)"]);
+        assert_eq!(split_species("Fe^{3+}"), ["Fe^{3+}"]);
+    }
+
+    #[test]
+    fn balances_ionic_equations() {
+        assert!(only("NH4+(aq) + H2O(l) ⇌ NH3(aq) + H3O+(aq)").is_correct());
+        assert!(only("Ba2+(aq) + SO42-(aq) → BaSO4(s)").is_correct());
+        assert!(only("NH₄⁺(aq) + H₂O(l) ⇌ NH₃(aq) + H₃O⁺(aq).").is_correct());
+    }
+
+    #[test]
+    fn checks_half_equations_without_states_on_electrons() {
+        assert!(only("Fe3+(aq) + e- -> Fe2+(aq)").is_correct());
+
+        let check = only("Cu2+(aq) + e- → Cu(s)");
+        assert!(check.atoms_balanced);
+        assert!(!check.charge_balanced);
+        assert_eq!(check.issues, ["Charge: +1 on the left, 0 on the right"]);
+    }
+
+    #[test]
+    fn counts_water_of_crystallisation() {
+        assert!(only("CuSO4·5H2O(s) → CuSO4(s) + 5H2O(g)").is_correct());
+        assert!(!only("CuSO4·5H2O(s) → CuSO4(s) + 4H2O(g)").atoms_balanced);
+    }
+
+    #[test]
+    fn unwraps_mhchem_and_latex_subscripts() {
+        let check = only("The reaction is \\ce{Fe^{3+}(aq) + 3OH-(aq) -> Fe(OH)3(s)} which forms a precipitate.");
+        assert_eq!(check.equation, "Fe^{3+}(aq) + 3OH-(aq) → Fe(OH)3(s)");
+        assert!(check.is_correct(), "{:?}", check.issues);
+
+        assert_eq!(normalise("NO_2 and NO_{2}"), "NO2 and NO2");
+        let check = only("$2NO_2 \\rightleftharpoons N2O4$");
+        assert!(check.atoms_balanced);
+        assert_eq!(check.missing_state_symbols, ["NO2", "N2O4"]);
+    }
+
+    #[test]
+    fn finds_equations_in_prose() {
+        let check = only("As HCl + NaOH → NaCl + H2O the solution is neutralised.");
+        assert_eq!(check.equation, "HCl + NaOH → NaCl + H2O");
+        assert!(check.atoms_balanced);
+
+        let checks = check_equations("BaCl2(aq) + Na2SO4(aq) → BaSO4(s) + 2NaCl(aq); then Ba2+(aq) + SO42-(aq) → BaSO4(s)");
+        assert_eq!(checks.len(), 2);
+        assert!(checks.iter().all(EquationCheck::is_correct));
+    }
+
+    #[test]
+    fn ignores_arrows_that_are_not_equations() {
+        assert!(check_equations("Step 1 -> 2").is_empty());
+        assert!(check_equations("Option C → B is wrong; pH → 7").is_empty());
+        assert!(check_equations("temperature ↑ → rate ↑").is_empty());
+    }
+}
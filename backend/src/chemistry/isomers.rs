@@ -0,0 +1,362 @@
+use serde::{Deserialize, Serialize};
+
+use super::nomenclature::{self, IsomerKind, OrganicCompound, HYPHENS};
+use crate::models::exam::IsomerAnswer;
+
+/// Words that say which kind of isomers a pair of names are.
+const KIND_WORDS: &[(&str, IsomerKind)] = &[
+    ("chain", IsomerKind::Chain),
+    ("skeletal", IsomerKind::Chain),
+    ("position", IsomerKind::Position),
+    ("functional", IsomerKind::FunctionalGroup),
+];
+const SENTENCE_ENDS: &[char] = &['.', ';', '\n', '!', '?'];
+
+/// The outcome of checking the isomers named in a response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IsomerCheck {
+    /// Every pair of compounds the student named as isomers of some kind.
+    pub pairs: Vec<NamedPair>,
+    pub score: f64,
+    /// Whether the names alone decide the mark. When they do not, for example
+    /// because a name could not be read, the response is graded by the model.
+    pub conclusive: bool,
+    /// Invalid names, labels without a pair of names and pairs not given.
+    #[serde(default)]
+    pub issues: Vec<String>,
+}
+
+/// A pair of names the student gave as isomers of the kind they claimed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NamedPair {
+    pub claimed: IsomerKind,
+    pub first: String,
+    pub second: String,
+    pub correct: bool,
+    /// e.g. `butanal and butan-2-one are functional group isomers, not chain isomers`.
+    pub comment: String,
+}
+
+enum Token {
+    Kind(IsomerKind),
+    Name(OrganicCompound),
+    /// A word meant as a name that is not a valid one, with the reason.
+    Invalid(String),
+}
+
+/// Finds the pairs of isomers named in a response, such as "chain isomers:
+/// butanal and 2-methylpropanal", and checks each against `expected`.
+pub fn check_isomers(text: &str, expected: &IsomerAnswer) -> IsomerCheck {
+    let formula = nomenclature::hill_formula(&expected.formula).unwrap_or_default();
+    let shown: Vec<OrganicCompound> = expected
+        .compounds
+        .iter()
+        .filter_map(|name| nomenclature::parse_name(name).ok())
+        .collect();
+    let mut check = IsomerCheck {
+        pairs: Vec::new(),
+        score: 0.0,
+        conclusive: true,
+        issues: Vec::new(),
+    };
+
+    let mut claims: Vec<(IsomerKind, Vec<OrganicCompound>)> = Vec::new();
+    for sentence in text.split(SENTENCE_ENDS) {
+        let tokens = tokens(sentence);
+        for token in &tokens {
+            if let Token::Invalid(reason) = token {
+                check.issues.push(reason.clone());
+                check.conclusive = false;
+            }
+        }
+
+        let (found, unlabelled) = sentence_claims(&tokens);
+        if unlabelled {
+            check.conclusive = false;
+        }
+        for (kind, names) in found {
+            // A pair may be split over two lines or fields, e.g. `chain_1: butanal`
+            // then `chain_2: 2-methylpropanal`
+            match claims.last_mut() {
+                Some((previous, half)) if *previous == kind && half.len() == 1 && names.len() == 1 => {
+                    half.push(names[0].clone())
+                }
+                _ => claims.push((kind, names.into_iter().cloned().collect())),
+            }
+        }
+    }
+
+    for (claimed, names) in claims {
+        let [first, second] = names.as_slice() else {
+            check.issues.push(match names.as_slice() {
+                [only] => format!("{} is given as a {} isomer without a second name", only.name, claimed),
+                _ => format!("{} names are given as {} isomers; expected a pair", names.len(), claimed),
+            });
+            check.conclusive = false;
+            continue;
+        };
+        let verdict = judge(first, second, claimed, &formula, &shown);
+        check.pairs.push(NamedPair {
+            claimed,
+            first: first.name.clone(),
+            second: second.name.clone(),
+            correct: verdict.is_ok(),
+            comment: verdict.unwrap_or_else(|reason| reason),
+        });
+    }
+
+    // Each required pair is awarded at most once, by a different correct pair
+    let mut used = vec![false; check.pairs.len()];
+    for required in &expected.pairs {
+        let found = check
+            .pairs
+            .iter()
+            .enumerate()
+            .position(|(i, pair)| !used[i] && pair.correct && pair.claimed == required.kind);
+        match found {
+            Some(i) => {
+                used[i] = true;
+                check.score += required.marks;
+            }
+            None if !check.pairs.iter().any(|pair| pair.claimed == required.kind) => {
+                check.issues.push(format!("No pair of {} isomers is named", required.kind));
+            }
+            None => {}
+        }
+    }
+
+    check.conclusive &= !check.pairs.is_empty();
+    check
+}
+
+fn judge(
+    first: &OrganicCompound,
+    second: &OrganicCompound,
+    claimed: IsomerKind,
+    formula: &str,
+    shown: &[OrganicCompound],
+) -> Result<String, String> {
+    for compound in [first, second] {
+        if compound.formula() != formula {
+            return Err(format!("{} is {}, not {}", compound.name, compound.formula(), formula));
+        }
+        if !shown.is_empty() && !shown.iter().any(|s| s.is_same_compound(compound)) {
+            return Err(format!("{} is not one of the compounds given", compound.name));
+        }
+    }
+    match first.isomerism(second)? {
+        actual if actual == claimed => Ok(format!(
+            "{} and {} are {} isomers of {}",
+            first.name, second.name, actual, formula
+        )),
+        actual => Err(format!(
+            "{} and {} are {} isomers, not {} isomers",
+            first.name, second.name, actual, claimed
+        )),
+    }
+}
+
+/// Groups the names in a sentence under the kind of isomers they are labelled
+/// as, whether the label comes first ("chain isomers: butanal and
+/// 2-methylpropanal") or last ("butanal and 2-methylpropanal are chain
+/// isomers"). Names already paired under the same label, mentioned again to
+/// explain the answer, are not a new claim. Also reports whether any name was
+/// left without a label.
+fn sentence_claims(tokens: &[Token]) -> (Vec<(IsomerKind, Vec<&OrganicCompound>)>, bool) {
+    let first_kind = tokens.iter().position(|t| matches!(t, Token::Kind(_)));
+    let first_name = tokens.iter().position(|t| matches!(t, Token::Name(_)));
+    let label_last = matches!((first_name, first_kind), (Some(name), Some(kind)) if name < kind);
+
+    let mut claims = Vec::new();
+    let mut names = Vec::new();
+    let mut current = None;
+    for token in tokens {
+        match token {
+            Token::Name(compound) => names.push(compound),
+            Token::Kind(kind) if label_last => {
+                if !names.is_empty() {
+                    claims.push((*kind, std::mem::take(&mut names)));
+                }
+            }
+            Token::Kind(kind) => {
+                if let Some(previous) = current.replace(*kind) {
+                    if !names.is_empty() {
+                        claims.push((previous, std::mem::take(&mut names)));
+                    }
+                }
+            }
+            Token::Invalid(_) => {}
+        }
+    }
+    if let (Some(kind), false) = (current, names.is_empty()) {
+        claims.push((kind, std::mem::take(&mut names)));
+    }
+
+    claims.dedup_by(|repeat, claim| {
+        repeat.0 == claim.0
+            && claim.1.len() == 2
+            && repeat.1.iter().all(|name| claim.1.iter().any(|paired| paired.is_same_compound(name)))
+    });
+    (claims, !names.is_empty())
+}
+
+fn tokens(sentence: &str) -> Vec<Token> {
+    let words = words(sentence);
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < words.len() {
+        let word = &words[i];
+        if let Some((_, kind)) = KIND_WORDS.iter().find(|(label, _)| word.starts_with(label)) {
+            tokens.push(Token::Kind(*kind));
+            i += 1;
+            continue;
+        }
+
+        // Acids and esters are named in two words, e.g. `methyl propanoate`
+        if let Some(next) = words.get(i + 1).filter(|next| *next == "acid" || next.ends_with("oate")) {
+            if let Ok(compound) = nomenclature::parse_name(&format!("{} {}", word, next)) {
+                tokens.push(Token::Name(compound));
+                i += 2;
+                continue;
+            }
+        }
+
+        match nomenclature::parse_name(word) {
+            Ok(compound) => tokens.push(Token::Name(compound)),
+            Err(reason) if nomenclature::looks_like_name(word) => tokens.push(Token::Invalid(reason)),
+            Err(_) => {}
+        }
+        i += 1;
+    }
+
+    tokens
+}
+
+/// Lowercase words, keeping the hyphens and locant commas inside names such
+/// as `2,2-dimethylpropane`.
+fn words(sentence: &str) -> Vec<String> {
+    let chars: Vec<char> = sentence.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+
+    for (i, &c) in chars.iter().enumerate() {
+        let locant_comma = c == ','
+            && i > 0
+            && chars[i - 1].is_ascii_digit()
+            && chars.get(i + 1).is_some_and(|next| next.is_ascii_digit());
+        if c.is_alphanumeric() || c == '-' || HYPHENS.contains(&c) || locant_comma {
+            word.extend(c.to_lowercase());
+        } else if !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+    }
+    words.push(word);
+
+    words
+        .into_iter()
+        .map(|word| word.trim_matches(|c| c == '-' || HYPHENS.contains(&c)).to_string())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ExamDefinition;
+
+    /// The isomers table of q21 in the HSC exam.
+    fn q21() -> IsomerAnswer {
+        let exam: ExamDefinition = toml::from_str(include_str!("../../../exams/hsc-chemistry.toml")).unwrap();
+        let q21 = exam.questions().find(|q| q.id == "q21").unwrap();
+        q21.isomers.clone().unwrap()
+    }
+
+    #[test]
+    fn reads_labels_before_and_after_the_names() {
+        let expected = q21();
+        let label_first = check_isomers(
+            "Functional group isomers: butan-2-one and butanal. Chain isomers: butanal and 2-methylpropanal.",
+            &expected,
+        );
+        let label_last = check_isomers(
+            "Butan-2-one and butanal are functional group isomers. Butanal and 2-methylpropanal are chain isomers.",
+            &expected,
+        );
+
+        for check in [&label_first, &label_last] {
+            assert_eq!(check.score, 2.0);
+            assert!(check.conclusive);
+            assert!(check.issues.is_empty(), "{:?}", check.issues);
+            assert_eq!(check.pairs.len(), 2);
+            assert!(check.pairs.iter().all(|pair| pair.correct));
+        }
+        assert_eq!(label_last.pairs[1].comment, "butanal and 2-methylpropanal are chain isomers of C4H8O");
+    }
+
+    #[test]
+    fn joins_pairs_split_across_fields() {
+        let response = "chain_1: butanal\nchain_2: 2-methylpropanal\nfunctional_1: butanone\nfunctional_2: butanal";
+        let check = check_isomers(response, &q21());
+
+        assert_eq!(check.score, 2.0);
+        assert!(check.conclusive);
+        assert_eq!(check.pairs[0].claimed, IsomerKind::Chain);
+        assert_eq!((check.pairs[0].first.as_str(), check.pairs[0].second.as_str()), ("butanal", "2-methylpropanal"));
+        assert_eq!(check.pairs[1].claimed, IsomerKind::FunctionalGroup);
+    }
+
+    #[test]
+    fn marks_a_pair_given_the_wrong_label() {
+        let check = check_isomers(
+            "Chain isomers: butanal and butan-2-one. Functional group isomers: butan-2-one and 2-methylpropanal.",
+            &q21(),
+        );
+
+        assert_eq!(check.score, 1.0);
+        assert!(check.conclusive);
+        assert!(!check.pairs[0].correct);
+        assert_eq!(
+            check.pairs[0].comment,
+            "butanal and butan-2-one are functional group isomers, not chain isomers"
+        );
+        assert!(check.pairs[1].correct);
+    }
+
+    #[test]
+    fn rejects_compounds_not_shown() {
+        let check = check_isomers(
+            "Chain isomers: butanal and 2-methylpropanal. Functional group isomers: butanal and but-3-en-1-ol.",
+            &q21(),
+        );
+
+        assert_eq!(check.score, 1.0);
+        assert!(!check.pairs[1].correct);
+        assert_eq!(check.pairs[1].comment, "but-3-en-1-ol is not one of the compounds given");
+    }
+
+    #[test]
+    fn leaves_unreadable_names_to_the_model() {
+        let check = check_isomers(
+            "Chain isomers: butanal and 2-methylpropanal. Functional group: butan-3-one and butanal.",
+            &q21(),
+        );
+
+        assert!(!check.conclusive);
+        assert_eq!(check.score, 1.0);
+        assert!(check.issues[0].contains("butan-3-one"), "{:?}", check.issues);
+        assert_eq!(check.issues[1], "butanal is given as a functional group isomer without a second name");
+    }
+
+    #[test]
+    fn is_inconclusive_without_any_pair() {
+        let check = check_isomers("I don't know", &q21());
+
+        assert!(!check.conclusive);
+        assert_eq!(check.score, 0.0);
+        assert_eq!(
+            check.issues,
+            ["No pair of functional group isomers is named", "No pair of chain isomers is named"]
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use serde::Serialize;
+
+use crate::models::{exam::QuestionDefinition, CriterionResult, QuestionFeedback};
+
+pub mod elements;
+pub mod equation;
+pub mod isomers;
+pub mod nomenclature;
+pub mod numeric;
+pub mod units;
+
+pub use equation::{check_equations, EquationCheck};
+pub use isomers::{check_isomers, IsomerCheck};
+pub use numeric::{check_numeric_answer, NumericCheck};
+
+/// Everything checked in a response without the model, shown to it in the
+/// grading prompt and recorded in the question's feedback.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ResponseChecks {
+    pub equations: Vec<EquationCheck>,
+    /// Only for questions with a `numeric_answer`.
+    pub numeric: Option<NumericCheck>,
+    /// Only for questions asking for `isomers`.
+    pub isomers: Option<IsomerCheck>,
+}
+
+impl ResponseChecks {
+    pub fn run(question: &QuestionDefinition, response_text: &str) -> Self {
+        Self {
+            equations: check_equations(response_text),
+            numeric: question
+                .numeric_answer
+                .as_ref()
+                .map(|expected| check_numeric_answer(response_text, expected)),
+            isomers: question
+                .isomers
+                .as_ref()
+                .map(|expected| check_isomers(response_text, expected)),
+        }
+    }
+
+    /// The mark for the response when the checks alone decide it, so it need
+    /// not be sent to a model.
+    pub fn conclusive_feedback(&self, question: &QuestionDefinition) -> Option<QuestionFeedback> {
+        let check = self.isomers.as_ref().filter(|check| check.conclusive)?;
+        let (correct, incorrect): (Vec<_>, Vec<_>) = check.pairs.iter().partition(|pair| pair.correct);
+
+        Some(QuestionFeedback {
+            question_id: question.id.clone(),
+            score: check.score,
+            max_score: question.marks,
+            feedback: format!(
+                "The isomers named were checked automatically, earning {} of {} marks.",
+                check.score, question.marks
+            ),
+            strengths: correct.iter().map(|pair| pair.comment.clone()).collect(),
+            improvements: incorrect
+                .iter()
+                .map(|pair| pair.comment.clone())
+                .chain(check.issues.iter().cloned())
+                .collect(),
+            criteria: question
+                .criteria
+                .iter()
+                .map(|criterion| CriterionResult {
+                    description: criterion.description.clone(),
+                    marks: criterion.marks,
+                    awarded: criterion.marks <= check.score,
+                    comment: None,
+                })
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    /// Copies the results onto the question's feedback.
+    pub fn record(self, feedback: QuestionFeedback) -> QuestionFeedback {
+        QuestionFeedback {
+            equation_checks: self.equations,
+            numeric_check: self.numeric,
+            isomer_check: self.isomers,
+            ..feedback
+        }
+    }
+}
@@ -0,0 +1,780 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::equation::{parse_formula, Composition};
+
+/// Parent chain stems by number of carbons; none is a prefix of another.
+const ROOTS: &[(&str, u32)] = &[
+    ("meth", 1),
+    ("eth", 2),
+    ("prop", 3),
+    ("but", 4),
+    ("pent", 5),
+    ("hex", 6),
+    ("hept", 7),
+    ("oct", 8),
+    ("non", 9),
+    ("dec", 10),
+];
+const MULTIPLIERS: &[(&str, usize)] = &[("di", 2), ("tri", 3), ("tetra", 4)];
+/// Prefixes that replace a hydrogen with an atom or group other than an alkyl.
+const HETERO_PREFIXES: &[(&str, Substituent)] = &[
+    ("fluoro", Substituent::Halogen("F")),
+    ("chloro", Substituent::Halogen("Cl")),
+    ("bromo", Substituent::Halogen("Br")),
+    ("iodo", Substituent::Halogen("I")),
+    ("hydroxy", Substituent::Hydroxy),
+    ("amino", Substituent::Amino),
+];
+/// Dashes that word processors substitute for a hyphen.
+pub const HYPHENS: &[char] = &['‐', '‑', '–', '—', '−'];
+
+/// The class of compound named by a suffix or, for hydrocarbons, by the
+/// bonds and prefixes present.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FunctionalGroup {
+    Alkane,
+    Alkene,
+    Alkyne,
+    Haloalkane,
+    Alcohol,
+    Aldehyde,
+    Ketone,
+    CarboxylicAcid,
+    Ester,
+    Amine,
+    Amide,
+}
+
+impl fmt::Display for FunctionalGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Alkane => "alkane",
+            Self::Alkene => "alkene",
+            Self::Alkyne => "alkyne",
+            Self::Haloalkane => "haloalkane",
+            Self::Alcohol => "alcohol",
+            Self::Aldehyde => "aldehyde",
+            Self::Ketone => "ketone",
+            Self::CarboxylicAcid => "carboxylic acid",
+            Self::Ester => "ester",
+            Self::Amine => "amine",
+            Self::Amide => "amide",
+        })
+    }
+}
+
+/// How two compounds with the same molecular formula differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IsomerKind {
+    /// Different carbon skeletons, e.g. butanal and 2-methylpropanal.
+    Chain,
+    /// The same skeleton and group in different places, e.g. propan-1-ol and propan-2-ol.
+    Position,
+    /// Different functional groups, e.g. butanal and butan-2-one.
+    FunctionalGroup,
+}
+
+impl fmt::Display for IsomerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Chain => "chain",
+            Self::Position => "position",
+            Self::FunctionalGroup => "functional group",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Substituent {
+    /// A straight alkyl chain of this many carbons.
+    Alkyl(u32),
+    Halogen(&'static str),
+    Hydroxy,
+    Amino,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Suffix {
+    Ol,
+    Al,
+    One,
+    Oic,
+    Oate,
+    Amine,
+    Amide,
+}
+
+impl Suffix {
+    fn parse(text: &str) -> Option<Self> {
+        Some(match text {
+            "ol" => Self::Ol,
+            "al" => Self::Al,
+            "one" => Self::One,
+            "oic" => Self::Oic,
+            "oate" => Self::Oate,
+            "amine" => Self::Amine,
+            "amide" => Self::Amide,
+            _ => return None,
+        })
+    }
+
+    /// Groups that can only be on the end carbon of a chain, which is numbered 1.
+    fn is_terminal(self) -> bool {
+        matches!(self, Self::Al | Self::Oic | Self::Oate | Self::Amide)
+    }
+
+    fn group(self) -> FunctionalGroup {
+        match self {
+            Self::Ol => FunctionalGroup::Alcohol,
+            Self::Al => FunctionalGroup::Aldehyde,
+            Self::One => FunctionalGroup::Ketone,
+            Self::Oic => FunctionalGroup::CarboxylicAcid,
+            Self::Oate => FunctionalGroup::Ester,
+            Self::Amine => FunctionalGroup::Amine,
+            Self::Amide => FunctionalGroup::Amide,
+        }
+    }
+}
+
+/// Where everything is on the parent chain, numbered as named.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Structure {
+    carbons: u32,
+    cyclic: bool,
+    /// Carbons bearing the group named by the suffix.
+    principal: Vec<u32>,
+    /// Lower-numbered carbon of each double bond.
+    double_bonds: Vec<u32>,
+    /// Lower-numbered carbon of each triple bond.
+    triple_bonds: Vec<u32>,
+    substituents: Vec<(u32, Substituent)>,
+    /// Carbons in the alkyl group on an ester's oxygen, e.g. 1 for methyl propanoate.
+    ester_alkyl: u32,
+}
+
+impl Structure {
+    /// The same structure numbered from the other end of the chain.
+    fn reflected(&self) -> Self {
+        let n = self.carbons;
+        let mut substituents: Vec<_> = self.substituents.iter().map(|(l, s)| (n + 1 - l, *s)).collect();
+        substituents.sort();
+        Self {
+            principal: sorted(self.principal.iter().map(|l| n + 1 - l)),
+            double_bonds: sorted(self.double_bonds.iter().map(|l| n - l)),
+            triple_bonds: sorted(self.triple_bonds.iter().map(|l| n - l)),
+            substituents,
+            ..self.clone()
+        }
+    }
+
+    /// One numbering for each compound, so differently numbered names can be compared.
+    fn canonical(&self) -> Self {
+        if self.cyclic {
+            self.clone()
+        } else {
+            self.clone().min(self.reflected())
+        }
+    }
+
+    /// The carbon skeleton alone: parent chain, alkyl branches and ester alkyl.
+    fn skeleton(&self) -> Self {
+        Self {
+            principal: Vec::new(),
+            double_bonds: Vec::new(),
+            triple_bonds: Vec::new(),
+            substituents: self
+                .substituents
+                .iter()
+                .filter(|(_, s)| matches!(s, Substituent::Alkyl(_)))
+                .copied()
+                .collect(),
+            ..self.clone()
+        }
+        .canonical()
+    }
+
+    /// Locants compared when choosing which end of the chain to number from:
+    /// the principal group first, then multiple bonds, then prefixes.
+    fn numbering_key(&self) -> (Vec<u32>, Vec<u32>, Vec<u32>) {
+        (
+            self.principal.clone(),
+            sorted(self.double_bonds.iter().chain(&self.triple_bonds).copied()),
+            sorted(self.substituents.iter().map(|(l, _)| *l)),
+        )
+    }
+}
+
+/// A compound identified from its IUPAC name.
+#[derive(Debug, Clone)]
+pub struct OrganicCompound {
+    /// The name as written.
+    pub name: String,
+    pub functional_group: FunctionalGroup,
+    composition: Composition,
+    structure: Structure,
+}
+
+impl OrganicCompound {
+    /// Molecular formula in Hill order, e.g. `C4H8O`.
+    pub fn formula(&self) -> String {
+        hill(&self.composition)
+    }
+
+    /// Whether both names describe the same compound, such as butanone and butan-2-one.
+    pub fn is_same_compound(&self, other: &OrganicCompound) -> bool {
+        self.structure.canonical() == other.structure.canonical()
+    }
+
+    /// How the two compounds are isomers, or why they are not.
+    pub fn isomerism(&self, other: &OrganicCompound) -> Result<IsomerKind, String> {
+        if self.composition != other.composition {
+            return Err(format!(
+                "{} is {} but {} is {}, so they are not isomers",
+                self.name,
+                self.formula(),
+                other.name,
+                other.formula()
+            ));
+        }
+        if self.is_same_compound(other) {
+            return Err(format!("{} and {} are the same compound", self.name, other.name));
+        }
+        if self.functional_group != other.functional_group {
+            Ok(IsomerKind::FunctionalGroup)
+        } else if self.structure.skeleton() != other.structure.skeleton() {
+            Ok(IsomerKind::Chain)
+        } else {
+            Ok(IsomerKind::Position)
+        }
+    }
+}
+
+/// A molecular formula such as `C₄H₈O` rewritten in Hill order, or `None` if it is not one.
+pub fn hill_formula(text: &str) -> Option<String> {
+    parse_formula(&text.replace(char::is_whitespace, "")).map(|composition| hill(&composition))
+}
+
+/// Whether a word is probably meant as an organic name, such as `butan-3-ol`
+/// or `propanol`, even if it is not a valid one.
+pub fn looks_like_name(word: &str) -> bool {
+    ROOTS.iter().any(|(root, _)| {
+        word.match_indices(root).any(|(i, _)| {
+            let after = &word[i + root.len()..];
+            ["an", "en", "yn", "a-", "-"].iter().any(|infix| after.starts_with(infix))
+        })
+    })
+}
+
+/// Parses names such as `butan-2-one`, `2-methylpropanal`, `but-2-ene`,
+/// `1,2-dichloroethane`, `propanoic acid` or `methyl ethanoate`, checking
+/// that the parent chain is the longest and the locants the lowest possible.
+pub fn parse_name(name: &str) -> Result<OrganicCompound, String> {
+    let written = name.trim();
+    let normalised: String = written
+        .to_lowercase()
+        .chars()
+        .map(|c| if HYPHENS.contains(&c) { '-' } else { c })
+        .collect();
+    let words: Vec<&str> = normalised.split_whitespace().collect();
+    let (alkyl, word, acid) = match words.as_slice() {
+        [word] => (None, *word, false),
+        [word, "acid"] => (None, *word, true),
+        [alkyl, word] => (Some(*alkyl), *word, false),
+        _ => return Err(format!("'{}' is not a compound name", written)),
+    };
+    let ester_alkyl = match alkyl {
+        Some(alkyl) => alkyl_size(alkyl).ok_or_else(|| format!("'{}' is not a compound name", written))?,
+        None => 0,
+    };
+
+    let (structure, suffix) = parse_word(word, written, ester_alkyl)?;
+    match (suffix, acid, ester_alkyl) {
+        (Some(Suffix::Oic), true, 0) | (Some(Suffix::Oate), false, 1..) => {}
+        (Some(Suffix::Oic), false, 0) => return Err(format!("{} is missing the word 'acid'", written)),
+        (Some(Suffix::Oate), false, 0) => {
+            return Err(format!("{} needs an alkyl group before it, e.g. methyl {}", written, word))
+        }
+        (Some(Suffix::Oic | Suffix::Oate), _, _) | (_, true, _) | (_, _, 1..) => {
+            return Err(format!("'{}' is not a compound name", written))
+        }
+        _ => {}
+    }
+
+    let functional_group = match suffix {
+        Some(suffix) => suffix.group(),
+        None if structure.substituents.iter().any(|(_, s)| *s == Substituent::Hydroxy) => FunctionalGroup::Alcohol,
+        None if structure.substituents.iter().any(|(_, s)| *s == Substituent::Amino) => FunctionalGroup::Amine,
+        None if !structure.triple_bonds.is_empty() => FunctionalGroup::Alkyne,
+        None if !structure.double_bonds.is_empty() => FunctionalGroup::Alkene,
+        None if structure.substituents.iter().any(|(_, s)| matches!(s, Substituent::Halogen(_))) => {
+            FunctionalGroup::Haloalkane
+        }
+        None => FunctionalGroup::Alkane,
+    };
+
+    Ok(OrganicCompound {
+        name: written.to_string(),
+        functional_group,
+        composition: composition(&structure, suffix, written)?,
+        structure,
+    })
+}
+
+/// Parses one word of a name: prefixes, the parent chain, its bonds and the suffix.
+fn parse_word(word: &str, written: &str, ester_alkyl: u32) -> Result<(Structure, Option<Suffix>), String> {
+    let mut rest = word;
+
+    // Prefixes such as `2,2-dimethyl-` or `1-chloro-`; locants with no
+    // prefix after them belong to the suffix, as in the older `2-butanol`
+    let mut prefixes = Vec::new();
+    let mut leading = loop {
+        let given = locants(&mut rest);
+        if given.is_some() && !eat(&mut rest, "-") {
+            return Err(format!("{} is missing a hyphen after its locants", written));
+        }
+        let before_multiplier = rest;
+        let count = multiplier(&mut rest);
+        match substituent(&mut rest) {
+            Some(substituent) => {
+                prefixes.push((given, count, substituent));
+                eat(&mut rest, "-");
+            }
+            None => {
+                rest = before_multiplier;
+                break given;
+            }
+        }
+    };
+
+    let cyclic = eat(&mut rest, "cyclo");
+    let carbons = ROOTS
+        .iter()
+        .find(|(root, _)| eat(&mut rest, root))
+        .map(|(_, carbons)| *carbons)
+        .ok_or_else(|| format!("'{}' has no parent chain such as meth-, eth- or prop-", written))?;
+    if cyclic && carbons < 3 {
+        return Err(format!("{} is impossible: a ring needs at least three carbons", written));
+    }
+
+    // Saturated `-an-`, or `-en-`/`-yn-` with optional locants, as in `but-2-ene` or `buta-1,3-diene`
+    let mut bonds = None;
+    if !eat(&mut rest, "an") {
+        let given = if eat(&mut rest, "a-") || eat(&mut rest, "-") {
+            let given = locants(&mut rest).ok_or_else(|| format!("{} has a misplaced hyphen", written))?;
+            if !eat(&mut rest, "-") {
+                return Err(format!("{} is missing a hyphen after its locants", written));
+            }
+            Some(given)
+        } else {
+            None
+        };
+        let count = multiplier(&mut rest);
+        let triple = if eat(&mut rest, "en") {
+            false
+        } else if eat(&mut rest, "yn") {
+            true
+        } else {
+            return Err(format!("'{}' should continue with -ane, -ene or -yne", written));
+        };
+        bonds = Some((given, count, triple));
+    }
+
+    // The suffix: `-e` for a hydrocarbon, or a group such as `-2-ol` or `-e-1,2-diol`
+    let mut principal = None;
+    let suffix = if rest == "e" {
+        None
+    } else {
+        let ending = rest;
+        eat(&mut rest, "e");
+        if eat(&mut rest, "-") {
+            principal = Some(locants(&mut rest).ok_or_else(|| format!("{} has a misplaced hyphen", written))?);
+            if !eat(&mut rest, "-") {
+                return Err(format!("{} is missing a hyphen after its locants", written));
+            }
+        }
+        let count = multiplier(&mut rest);
+        let suffix = Suffix::parse(rest).ok_or_else(|| format!("'{}' has an unknown ending '-{}'", written, ending))?;
+        Some((suffix, count))
+    };
+
+    let mut structure = Structure {
+        carbons,
+        cyclic,
+        principal: Vec::new(),
+        double_bonds: Vec::new(),
+        triple_bonds: Vec::new(),
+        substituents: Vec::new(),
+        ester_alkyl,
+    };
+    let n = carbons;
+
+    if let Some((suffix, count)) = suffix {
+        if suffix.is_terminal() && cyclic {
+            return Err(format!("{} is not supported: rings with this group are named differently", written));
+        }
+        if suffix == Suffix::Oate && count != 1 {
+            return Err(format!("{} is not supported", written));
+        }
+        let given = match (principal, leading.take()) {
+            (Some(_), Some(_)) => return Err(format!("{} has locants both before and after the parent", written)),
+            (given, leading) => given.or(leading),
+        };
+        let locants = match given {
+            Some(locants) => locants,
+            None => default_principal(suffix, count, n, cyclic)
+                .ok_or_else(|| format!("{} needs a locant to show which carbon the group is on", written))?,
+        };
+        if locants.len() != count {
+            return Err(format!("{} gives {} locants for {} groups", written, locants.len(), count));
+        }
+        for &locant in &locants {
+            if !(1..=n).contains(&locant) {
+                return Err(format!("{} has no carbon {}", written, locant));
+            }
+            if suffix.is_terminal() && locant != 1 && locant != n {
+                return Err(format!("{} is impossible: this group can only be at the end of the chain", written));
+            }
+            if suffix == Suffix::One && !cyclic && (locant == 1 || locant == n) {
+                return Err(format!("{} is impossible: a C=O at the end of a chain is an aldehyde, not a ketone", written));
+            }
+        }
+        structure.principal = sorted(locants);
+    }
+
+    if let Some((given, count, triple)) = bonds {
+        let given = given.or_else(|| leading.take());
+        let locants = match given {
+            Some(locants) => locants,
+            None => default_bond(n, cyclic, suffix.map(|(s, _)| s), count)
+                .ok_or_else(|| format!("{} needs a locant to show where the multiple bond is", written))?,
+        };
+        if locants.len() != count {
+            return Err(format!("{} gives {} locants for {} multiple bonds", written, locants.len(), count));
+        }
+        let last = if cyclic { n } else { n - 1 };
+        if let Some(locant) = locants.iter().find(|l| !(1..=last).contains(*l)) {
+            return Err(format!("{} has no bond starting at carbon {}", written, locant));
+        }
+        if triple {
+            structure.triple_bonds = sorted(locants);
+        } else {
+            structure.double_bonds = sorted(locants);
+        }
+    }
+    if leading.is_some() {
+        return Err(format!("{} has a locant with nothing to refer to", written));
+    }
+
+    // Prefixes without locants are only allowed where there is a single place for them
+    let (located, unlocated): (Vec<_>, Vec<_>) = prefixes.into_iter().partition(|(given, _, _)| given.is_some());
+    for (given, count, substituent) in located {
+        let locants = given.unwrap_or_default();
+        if locants.len() != count {
+            return Err(format!("{} gives {} locants for {} groups", written, locants.len(), count));
+        }
+        for locant in locants {
+            structure.substituents.push((locant, substituent));
+        }
+    }
+    let terminal = suffix.is_some_and(|(s, _)| s.is_terminal());
+    for (_, count, substituent) in unlocated {
+        let locants = default_substituent(&structure, terminal, substituent, count)
+            .ok_or_else(|| format!("{} needs locants to show where each group is", written))?;
+        for locant in locants {
+            structure.substituents.push((locant, substituent));
+        }
+    }
+    structure.substituents.sort();
+
+    for &(locant, substituent) in &structure.substituents {
+        if !(1..=n).contains(&locant) {
+            return Err(format!("{} has no carbon {}", written, locant));
+        }
+        if let Substituent::Alkyl(size) = substituent {
+            if !cyclic && (locant <= size || locant + size > n) {
+                return Err(format!(
+                    "{} does not use the longest chain: the {}yl group on carbon {} extends it",
+                    written,
+                    ROOTS[size as usize - 1].0,
+                    locant
+                ));
+            }
+        }
+    }
+
+    if !cyclic && !terminal && structure.reflected().numbering_key() < structure.numbering_key() {
+        return Err(format!(
+            "{} is numbered from the wrong end; the locants should be as low as possible",
+            written
+        ));
+    }
+
+    Ok((structure, suffix.map(|(s, _)| s)))
+}
+
+fn composition(structure: &Structure, suffix: Option<Suffix>, written: &str) -> Result<Composition, String> {
+    let n = structure.carbons as i64;
+    let mut carbon = n + structure.ester_alkyl as i64;
+    let mut hydrogen = if structure.cyclic { 2 * n } else { 2 * n + 2 } + 2 * structure.ester_alkyl as i64;
+    let mut oxygen = 0;
+    let mut nitrogen = 0;
+    let mut composition = Composition::new();
+
+    hydrogen -= 2 * structure.double_bonds.len() as i64 + 4 * structure.triple_bonds.len() as i64;
+    for (_, substituent) in &structure.substituents {
+        match substituent {
+            Substituent::Alkyl(size) => {
+                carbon += *size as i64;
+                hydrogen += 2 * *size as i64;
+            }
+            Substituent::Halogen(symbol) => {
+                hydrogen -= 1;
+                *composition.entry(symbol.to_string()).or_default() += 1.0;
+            }
+            Substituent::Hydroxy => oxygen += 1,
+            Substituent::Amino => {
+                nitrogen += 1;
+                hydrogen += 1;
+            }
+        }
+    }
+    for _ in &structure.principal {
+        match suffix {
+            Some(Suffix::Ol) => oxygen += 1,
+            Some(Suffix::Al | Suffix::One) => {
+                oxygen += 1;
+                hydrogen -= 2;
+            }
+            Some(Suffix::Oic | Suffix::Oate) => {
+                oxygen += 2;
+                hydrogen -= 2;
+            }
+            Some(Suffix::Amine) => {
+                nitrogen += 1;
+                hydrogen += 1;
+            }
+            Some(Suffix::Amide) => {
+                oxygen += 1;
+                nitrogen += 1;
+                hydrogen -= 1;
+            }
+            None => {}
+        }
+    }
+
+    if hydrogen < 0 {
+        return Err(format!("{} is impossible: its carbons have too many bonds", written));
+    }
+    for (symbol, count) in [("C", carbon), ("H", hydrogen), ("O", oxygen), ("N", nitrogen)] {
+        if count > 0 {
+            composition.insert(symbol.to_string(), count as f64);
+        }
+    }
+    Ok(composition)
+}
+
+/// Where a suffix goes when the name gives no locant, if there is only one choice.
+fn default_principal(suffix: Suffix, count: usize, carbons: u32, cyclic: bool) -> Option<Vec<u32>> {
+    match (suffix, count) {
+        (s, 1) if s.is_terminal() => Some(vec![1]),
+        (s, 2) if s.is_terminal() => Some(vec![1, carbons]),
+        (Suffix::Ol | Suffix::Amine, 1) if carbons <= 2 || cyclic => Some(vec![1]),
+        (Suffix::One, 1) if cyclic => Some(vec![1]),
+        (Suffix::One, 1) if (3..=4).contains(&carbons) => Some(vec![2]),
+        _ => None,
+    }
+}
+
+/// Where a multiple bond goes when the name gives no locant, as in propene or propenal.
+fn default_bond(carbons: u32, cyclic: bool, suffix: Option<Suffix>, count: usize) -> Option<Vec<u32>> {
+    match suffix {
+        _ if count != 1 => None,
+        _ if cyclic => Some(vec![1]),
+        None if carbons <= 3 => Some(vec![1]),
+        Some(s) if s.is_terminal() && carbons == 3 => Some(vec![2]),
+        _ => None,
+    }
+}
+
+/// Where prefixes go when the name gives no locants, as in chloroethane,
+/// methylpropane or trichloromethane, if there is only one choice.
+fn default_substituent(structure: &Structure, terminal: bool, substituent: Substituent, count: usize) -> Option<Vec<u32>> {
+    let n = structure.carbons;
+    if n == 1 {
+        return Some(vec![1; count]);
+    }
+    if count != 1 {
+        return None;
+    }
+
+    let mut candidates: Vec<u32> = (1..=n)
+        .filter(|l| match substituent {
+            Substituent::Alkyl(size) => structure.cyclic || (*l > size && *l + size <= n),
+            // The carbon of an aldehyde, acid, ester or amide group has no hydrogen to replace
+            _ => !(terminal && structure.principal.contains(l)),
+        })
+        .collect();
+    // Positions that mirror each other on an otherwise symmetric chain are the same compound
+    if structure.cyclic || *structure == structure.reflected() {
+        candidates.retain(|l| *l <= n + 1 - l);
+    }
+    match candidates.as_slice() {
+        [only] => Some(vec![*only]),
+        _ => None,
+    }
+}
+
+fn alkyl_size(word: &str) -> Option<u32> {
+    ROOTS
+        .iter()
+        .find(|(root, _)| word.strip_prefix(root) == Some("yl"))
+        .map(|(_, carbons)| *carbons)
+}
+
+fn substituent(rest: &mut &str) -> Option<Substituent> {
+    if let Some((_, substituent)) = HETERO_PREFIXES.iter().find(|(prefix, _)| eat(rest, prefix)) {
+        return Some(*substituent);
+    }
+    ROOTS.iter().find_map(|(root, carbons)| {
+        let remaining = rest.strip_prefix(root)?.strip_prefix("yl")?;
+        *rest = remaining;
+        Some(Substituent::Alkyl(*carbons))
+    })
+}
+
+fn multiplier(rest: &mut &str) -> usize {
+    MULTIPLIERS
+        .iter()
+        .find(|(prefix, _)| eat(rest, prefix))
+        .map_or(1, |(_, count)| *count)
+}
+
+/// Reads locants such as `2` or `1,3`, leaving `rest` unchanged if there are none.
+fn locants(rest: &mut &str) -> Option<Vec<u32>> {
+    if !rest.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let end = rest.find(|c: char| !c.is_ascii_digit() && c != ',').unwrap_or(rest.len());
+    let locants = rest[..end].split(',').map(|l| l.parse().ok()).collect::<Option<Vec<u32>>>()?;
+    *rest = &rest[end..];
+    Some(locants)
+}
+
+fn eat(rest: &mut &str, prefix: &str) -> bool {
+    match rest.strip_prefix(prefix) {
+        Some(remaining) => {
+            *rest = remaining;
+            true
+        }
+        None => false,
+    }
+}
+
+fn sorted(locants: impl IntoIterator<Item = u32>) -> Vec<u32> {
+    let mut locants: Vec<u32> = locants.into_iter().collect();
+    locants.sort_unstable();
+    locants
+}
+
+/// Carbon first, then hydrogen, then everything else alphabetically.
+fn hill(composition: &Composition) -> String {
+    let mut symbols: Vec<&String> = composition.keys().collect();
+    if composition.contains_key("C") {
+        symbols.sort_by_key(|symbol| (symbol.as_str() != "C", symbol.as_str() != "H", symbol.as_str()));
+    }
+    symbols
+        .into_iter()
+        .map(|symbol| {
+            let count = composition[symbol];
+            if count == 1.0 {
+                symbol.clone()
+            } else {
+                format!("{}{}", symbol, count)
+            }
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(name: &str) -> String {
+        parse_name(name).unwrap_err()
+    }
+
+    fn isomerism(first: &str, second: &str) -> Result<IsomerKind, String> {
+        parse_name(first).unwrap().isomerism(&parse_name(second).unwrap())
+    }
+
+    #[test]
+    fn reads_names_with_their_formula_and_group() {
+        for (name, formula, group) in [
+            ("butan-2-one", "C4H8O", FunctionalGroup::Ketone),
+            ("butanal", "C4H8O", FunctionalGroup::Aldehyde),
+            ("2-methylpropanal", "C4H8O", FunctionalGroup::Aldehyde),
+            ("2,2-dimethylpropane", "C5H12", FunctionalGroup::Alkane),
+            ("pent-2-yne", "C5H8", FunctionalGroup::Alkyne),
+            ("1,1-dichloroethane", "C2H4Cl2", FunctionalGroup::Haloalkane),
+            ("cyclobutanol", "C4H8O", FunctionalGroup::Alcohol),
+            ("propanoic acid", "C3H6O2", FunctionalGroup::CarboxylicAcid),
+            ("methyl ethanoate", "C3H6O2", FunctionalGroup::Ester),
+        ] {
+            let compound = parse_name(name).unwrap();
+            assert_eq!(compound.formula(), formula, "{}", name);
+            assert_eq!(compound.functional_group, group, "{}", name);
+        }
+        assert_eq!(hill_formula("C₄H₈O").as_deref(), Some("C4H8O"));
+    }
+
+    #[test]
+    fn rejects_numbering_from_the_wrong_end() {
+        assert_eq!(
+            error("butan-3-ol"),
+            "butan-3-ol is numbered from the wrong end; the locants should be as low as possible"
+        );
+        assert_eq!(
+            error("3-methylbutane"),
+            "3-methylbutane is numbered from the wrong end; the locants should be as low as possible"
+        );
+        assert!(error("but-3-ene").contains("numbered from the wrong end"));
+    }
+
+    #[test]
+    fn rejects_a_chain_that_is_not_the_longest() {
+        assert_eq!(
+            error("2-ethylbutane"),
+            "2-ethylbutane does not use the longest chain: the ethyl group on carbon 2 extends it"
+        );
+    }
+
+    #[test]
+    fn explains_other_invalid_names() {
+        assert_eq!(
+            error("butan-1-one"),
+            "butan-1-one is impossible: a C=O at the end of a chain is an aldehyde, not a ketone"
+        );
+        assert_eq!(error("ethanoate"), "ethanoate needs an alkyl group before it, e.g. methyl ethanoate");
+        assert!(looks_like_name("butan-3-ol"));
+        assert!(!looks_like_name("isomers"));
+    }
+
+    #[test]
+    fn classifies_isomers() {
+        assert_eq!(isomerism("butan-1-ol", "butan-2-ol"), Ok(IsomerKind::Position));
+        assert_eq!(isomerism("butan-1-ol", "2-methylpropan-1-ol"), Ok(IsomerKind::Chain));
+        assert_eq!(isomerism("butanal", "butan-2-one"), Ok(IsomerKind::FunctionalGroup));
+        assert_eq!(
+            isomerism("butanone", "butan-2-one"),
+            Err("butanone and butan-2-one are the same compound".to_string())
+        );
+        assert_eq!(
+            isomerism("butan-1-ol", "butanal"),
+            Err("butan-1-ol is C4H10O but butanal is C4H8O, so they are not isomers".to_string())
+        );
+    }
+}
@@ -0,0 +1,429 @@
+use serde::{Deserialize, Serialize};
+
+use super::units::Unit;
+use crate::models::exam::NumericAnswer;
+
+/// Words after which the rest of the working is taken to be the final answer.
+const ANSWER_MARKERS: &[&str] = &["answer", "∴", "therefore", "hence", "final"];
+/// Tolerance used when the definition gives none, as a fraction of the expected value.
+const DEFAULT_RELATIVE_TOLERANCE: f64 = 0.01;
+/// Notes on precision, such as `(3 s.f.)`, whose numbers are not answers.
+const PRECISION_NOTES: &[&str] = &["s.f", "sf", "sig", "significant", "d.p", "dp", "decimal"];
+/// Most tokens read after a number when looking for its unit, e.g. `J g-1 K-1`.
+const MAX_UNIT_TOKENS: usize = 4;
+
+/// The outcome of checking the final answer of a calculation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NumericCheck {
+    /// The expected answer, e.g. `0.774 g to 3 significant figures`.
+    pub expected: String,
+    /// The student's final answer as written, if one was found.
+    pub answer: Option<String>,
+    pub value_correct: bool,
+    pub unit_correct: bool,
+    pub significant_figures_correct: bool,
+    #[serde(default)]
+    pub issues: Vec<String>,
+}
+
+impl NumericCheck {
+    pub fn is_correct(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        match &self.answer {
+            Some(answer) if self.is_correct() => format!("{} is correct (expected {})", answer, self.expected),
+            Some(answer) => format!("{}: {}", answer, self.issues.join("; ")),
+            None => self.issues.join("; "),
+        }
+    }
+}
+
+/// A number in the working, with the unit written after it if any.
+struct Quantity {
+    written: String,
+    value: f64,
+    /// Fewest and most significant figures the number could have been given
+    /// to; they differ for whole numbers ending in zeros, such as `1200`.
+    significant_figures: (usize, usize),
+    unit: Option<Unit>,
+}
+
+/// Finds the final answer in a student's working and checks its value, unit
+/// and significant figures against `expected`.
+pub fn check_numeric_answer(text: &str, expected: &NumericAnswer) -> NumericCheck {
+    let expected_unit = expected.unit.as_deref().and_then(|u| Unit::parse(u).ok());
+    let mut check = NumericCheck {
+        expected: describe(expected),
+        answer: None,
+        value_correct: false,
+        unit_correct: false,
+        significant_figures_correct: false,
+        issues: Vec::new(),
+    };
+
+    let Some(answer) = final_answer(text, expected_unit.as_ref()) else {
+        check.issues.push("No final answer was found in the working".to_string());
+        return check;
+    };
+    check.answer = Some(answer.written.clone());
+
+    // Compare in the expected unit, converting from the student's where possible
+    let unit_name = expected.unit.as_deref().unwrap_or_default();
+    let value = match (&answer.unit, &expected_unit) {
+        (_, None) => {
+            check.unit_correct = true;
+            answer.value
+        }
+        (Some(unit), Some(target)) if unit.is_compatible(target) => {
+            check.unit_correct = true;
+            unit.convert(answer.value, target).unwrap_or(answer.value)
+        }
+        (Some(_), Some(_)) => {
+            check.issues.push(format!("The unit cannot be converted to {}", unit_name));
+            answer.value
+        }
+        (None, Some(_)) => {
+            check.issues.push(format!("No unit was given; expected {}", unit_name));
+            answer.value
+        }
+    };
+
+    let tolerance = expected
+        .tolerance
+        .unwrap_or(expected.value.abs() * DEFAULT_RELATIVE_TOLERANCE);
+    check.value_correct = (value - expected.value).abs() <= tolerance + f64::EPSILON * expected.value.abs().max(1.0);
+    if !check.value_correct {
+        check.issues.push(format!(
+            "The value is outside {} ± {}",
+            with_unit(expected.value, expected.unit.as_deref()),
+            to_three_figures(tolerance)
+        ));
+    }
+
+    let (fewest, most) = answer.significant_figures;
+    check.significant_figures_correct = match expected.significant_figures {
+        Some(required) => (fewest..=most).contains(&required),
+        None => true,
+    };
+    if let (false, Some(required)) = (check.significant_figures_correct, expected.significant_figures) {
+        check.issues.push(format!(
+            "Given to {} significant figures; {} required",
+            if fewest == most { fewest.to_string() } else { format!("{}-{}", fewest, most) },
+            required
+        ));
+    }
+
+    check
+}
+
+fn describe(expected: &NumericAnswer) -> String {
+    let answer = with_unit(expected.value, expected.unit.as_deref());
+    match expected.significant_figures {
+        Some(figures) => format!("{} to {} significant figures", answer, figures),
+        None => answer,
+    }
+}
+
+fn with_unit(value: f64, unit: Option<&str>) -> String {
+    match unit {
+        Some(unit) => format!("{} {}", value, unit),
+        None => value.to_string(),
+    }
+}
+
+/// Formats a derived value such as a tolerance without floating-point noise.
+fn to_three_figures(value: f64) -> String {
+    if value == 0.0 || !value.is_finite() {
+        return value.to_string();
+    }
+    let decimals = (2 - value.abs().log10().floor() as i32).max(0) as usize;
+    let rounded: f64 = format!("{:.*}", decimals, value).parse().unwrap_or(value);
+    rounded.to_string()
+}
+
+/// The last quantity after the last answer marker, preferring one whose unit
+/// matches the expected unit over a bare number or an intermediate result.
+fn final_answer(text: &str, expected_unit: Option<&Unit>) -> Option<Quantity> {
+    let lower = text.to_ascii_lowercase();
+    let answer_start = ANSWER_MARKERS
+        .iter()
+        .filter_map(|marker| lower.rfind(marker))
+        .max()
+        .filter(|start| !quantities(&text[*start..]).is_empty())
+        .unwrap_or(0);
+
+    let mut candidates = quantities(&text[answer_start..]);
+    let compatible = candidates.iter().rposition(|q| match (&q.unit, expected_unit) {
+        (Some(unit), Some(expected)) => unit.is_compatible(expected),
+        _ => false,
+    });
+    match compatible {
+        Some(index) => Some(candidates.swap_remove(index)),
+        None => candidates.pop(),
+    }
+}
+
+/// Every number in the text that is not part of a formula such as `H2O`.
+fn quantities(text: &str) -> Vec<Quantity> {
+    let mut found = Vec::new();
+    let mut pos = 0;
+
+    while pos < text.len() {
+        let rest = &text[pos..];
+        let previous = text[..pos].chars().next_back();
+        let starts_number = rest.starts_with(|c: char| c.is_ascii_digit())
+            || (rest.starts_with(['-', '−', '.']) && rest[rest.chars().next().map_or(1, char::len_utf8)..].starts_with(|c: char| c.is_ascii_digit()));
+
+        if !starts_number {
+            pos += rest.chars().next().map_or(1, char::len_utf8);
+            continue;
+        }
+        if previous.is_some_and(|p| p.is_alphanumeric() || matches!(p, '.' | '^' | '_')) {
+            // Skip the rest of a formula, word or exponent so its digits are not read as numbers
+            pos += rest.chars().next().map_or(1, char::len_utf8);
+            pos += text[pos..].find(|c: char| !c.is_alphanumeric()).unwrap_or(text.len() - pos);
+            continue;
+        }
+
+        match read_quantity(rest) {
+            Some((quantity, length)) => {
+                let note = text[pos + length..].trim_start().to_ascii_lowercase();
+                if quantity.unit.is_some() || !PRECISION_NOTES.iter().any(|n| note.starts_with(n)) {
+                    found.push(quantity);
+                }
+                pos += length;
+            }
+            None => pos += rest.chars().next().map_or(1, char::len_utf8),
+        }
+    }
+
+    found
+}
+
+/// Reads a number such as `-5294`, `0.774`, `31 446`, `6.0e-3` or
+/// `1.2 × 10⁻³`, followed by its unit if one is written.
+fn read_quantity(text: &str) -> Option<(Quantity, usize)> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let byte_at = |i: usize| chars.get(i).map_or(text.len(), |(b, _)| *b);
+    let digit_at = |i: usize| chars.get(i).is_some_and(|(_, c)| c.is_ascii_digit());
+
+    let mut i = 0;
+    let mut mantissa = String::new();
+    if matches!(chars[0].1, '-' | '−') {
+        mantissa.push('-');
+        i += 1;
+    }
+
+    let mut group_length = 0;
+    while i < chars.len() {
+        let c = chars[i].1;
+        if c.is_ascii_digit() {
+            mantissa.push(c);
+            group_length += 1;
+            i += 1;
+        } else if matches!(c, ' ' | '\u{2009}' | '\u{202F}' | ',')
+            && !mantissa.contains('.')
+            && group_length <= 3
+            && (1..=3).all(|k| digit_at(i + k))
+            && !digit_at(i + 4)
+        {
+            // Thousands separator, as in 31 446
+            group_length = 0;
+            i += 1;
+        } else if c == '.' && !mantissa.contains('.') && digit_at(i + 1) {
+            mantissa.push('.');
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    let significant_figures = significant_figures(&mantissa);
+    let mut value: f64 = mantissa.parse().ok()?;
+    let mut end = i;
+
+    if let Some((exponent, length)) = read_exponent(&text[byte_at(end)..]) {
+        value *= 10f64.powi(exponent);
+        end = text[..byte_at(end) + length].chars().count();
+    }
+    let number_end = byte_at(end);
+
+    // The unit is the longest run of following tokens that parses, e.g. `kJ mol-1`
+    let after = &text[number_end..];
+    let line_end = after.find('\n').unwrap_or(after.len());
+    let mut unit = None;
+    let mut unit_end = number_end;
+    let mut token_end = 0;
+    for _ in 0..MAX_UNIT_TOKENS {
+        let rest = &after[token_end..line_end];
+        let start = token_end + (rest.len() - rest.trim_start().len());
+        if start >= line_end {
+            break;
+        }
+        token_end = after[start..line_end].find(char::is_whitespace).map_or(line_end, |i| start + i);
+        let candidate = after[..token_end].trim().trim_end_matches(['.', ',', ';', ':', ')']);
+        if let Ok(parsed) = Unit::parse(candidate) {
+            unit = Some(parsed);
+            unit_end = number_end + token_end;
+        }
+    }
+
+    let written = text[..unit_end].trim().trim_end_matches(['.', ',', ';', ':']).to_string();
+    Some((
+        Quantity {
+            written,
+            value,
+            significant_figures,
+            unit,
+        },
+        unit_end.max(number_end),
+    ))
+}
+
+/// Reads `e-3`, `E5`, `× 10^-3`, `x10-3`, `*10**-3` or `× 10⁻³` after a number.
+fn read_exponent(text: &str) -> Option<(i32, usize)> {
+    if let Some(rest) = text.strip_prefix(['e', 'E']) {
+        let (exponent, length) = read_integer(rest)?;
+        return Some((exponent, 1 + length));
+    }
+
+    let trimmed = text.trim_start();
+    let times = trimmed.strip_prefix(['×', 'x', '*'])?;
+    let ten = times.trim_start().strip_prefix("10")?;
+    let power = ten.strip_prefix("**").or_else(|| ten.strip_prefix('^')).unwrap_or(ten);
+    let braced = power.strip_prefix('{');
+    let (exponent, length) = read_integer(braced.unwrap_or(power))?;
+    let mut consumed = text.len() - power.len() + length;
+    if braced.is_some() {
+        consumed += 1 + usize::from(power[1 + length..].starts_with('}'));
+    }
+    Some((exponent, consumed))
+}
+
+/// Reads a signed integer in ASCII or superscript digits, returning its byte length.
+fn read_integer(text: &str) -> Option<(i32, usize)> {
+    let mut digits = String::new();
+    let mut length = 0;
+    for c in text.chars() {
+        let normal = match c {
+            '-' | '−' | '⁻' if digits.is_empty() => '-',
+            '+' | '⁺' if digits.is_empty() => '+',
+            '0'..='9' => c,
+            '⁰' => '0',
+            '¹' => '1',
+            '²' => '2',
+            '³' => '3',
+            '⁴'..='⁹' => char::from_digit(c as u32 - '⁴' as u32 + 4, 10)?,
+            _ => break,
+        };
+        digits.push(normal);
+        length += c.len_utf8();
+    }
+    Some((digits.parse().ok()?, length))
+}
+
+fn significant_figures(mantissa: &str) -> (usize, usize) {
+    let digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+    let significant = digits.trim_start_matches('0');
+    if significant.is_empty() {
+        return (1, 1);
+    }
+    if mantissa.contains('.') {
+        return (significant.len(), significant.len());
+    }
+    (significant.trim_end_matches('0').len(), significant.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ExamDefinition;
+
+    fn answer(value: f64, unit: &str, significant_figures: Option<usize>) -> NumericAnswer {
+        NumericAnswer {
+            value,
+            unit: Some(unit.to_string()),
+            tolerance: None,
+            significant_figures,
+        }
+    }
+
+    #[test]
+    fn checks_the_q25a_working_from_the_exam_file() {
+        let exam: ExamDefinition = toml::from_str(include_str!("../../../exams/hsc-chemistry.toml")).unwrap();
+        let q25a = exam.questions().find(|q| q.id == "q25a").unwrap();
+        let expected = q25a.numeric_answer.as_ref().unwrap();
+
+        let working = "q = mcΔT = 205 × 4.18 × 36.7 = 31 448 J\n\
+                       n = 31.448 / 5294 = 0.00594 mol\n\
+                       m = 0.00594 × 130.23 = 0.774 g";
+        let check = check_numeric_answer(working, expected);
+        assert!(check.is_correct(), "{:?}", check.issues);
+        assert_eq!(check.answer.as_deref(), Some("0.774 g"));
+        assert_eq!(check.expected, "0.774 g to 3 significant figures");
+
+        let check = check_numeric_answer("m = 0.77 g", expected);
+        assert!(check.value_correct);
+        assert_eq!(check.issues, ["Given to 2 significant figures; 3 required"]);
+    }
+
+    #[test]
+    fn converts_the_answer_and_skips_precision_notes() {
+        let check = check_numeric_answer("Therefore the mass is 774 mg (3 s.f.)", &answer(0.774, "g", Some(3)));
+        assert!(check.is_correct(), "{:?}", check.issues);
+        assert_eq!(check.answer.as_deref(), Some("774 mg"));
+
+        let check = check_numeric_answer("c = 0.1 M", &answer(0.1, "mol/L", None));
+        assert!(check.is_correct(), "{:?}", check.issues);
+    }
+
+    #[test]
+    fn reads_thousands_groups_and_powers_of_ten() {
+        let energy = answer(31_448.0, "J", None);
+        assert!(check_numeric_answer("q = 31 448 J", &energy).is_correct());
+        assert!(check_numeric_answer("q = 31.4 kJ", &energy).is_correct());
+
+        let moles = answer(1.2e-3, "mol", Some(2));
+        for working in ["n = 1.2 × 10⁻³ mol", "n = 1.2e-3 mol", "n=1.2x10^-3 mol"] {
+            let check = check_numeric_answer(working, &moles);
+            assert!(check.is_correct(), "{}: {:?}", working, check.issues);
+        }
+    }
+
+    #[test]
+    fn accepts_spelled_out_units() {
+        let check = check_numeric_answer("The mass burnt is 0.774 grams.", &answer(0.774, "g", Some(3)));
+        assert!(check.is_correct(), "{:?}", check.issues);
+        assert_eq!(check.answer.as_deref(), Some("0.774 grams"));
+    }
+
+    #[test]
+    fn reports_missing_and_wrong_units() {
+        let mass = answer(0.774, "g", Some(3));
+        assert_eq!(check_numeric_answer("mass = 0.774", &mass).issues, ["No unit was given; expected g"]);
+        assert_eq!(
+            check_numeric_answer("0.774 mL", &mass).issues,
+            ["The unit cannot be converted to g"]
+        );
+        assert_eq!(
+            check_numeric_answer("m = 0.800 g", &mass).issues,
+            ["The value is outside 0.774 g ± 0.00774"]
+        );
+    }
+
+    #[test]
+    fn ignores_digits_in_formulas() {
+        let check = check_numeric_answer("I used H2O and C8H17OH but didn't finish", &answer(0.774, "g", Some(3)));
+        assert_eq!(check.answer, None);
+        assert_eq!(check.issues, ["No final answer was found in the working"]);
+    }
+
+    #[test]
+    fn counts_significant_figures() {
+        assert_eq!(significant_figures("0.00594"), (3, 3));
+        assert_eq!(significant_figures("1200"), (2, 4));
+        assert_eq!(significant_figures("1200.0"), (5, 5));
+        assert_eq!(significant_figures("0"), (1, 1));
+    }
+}
@@ -0,0 +1,280 @@
+/// Exponents of mass, length, time, amount of substance and temperature.
+type Dimensions = [i32; 5];
+
+const DIMENSIONLESS: Dimensions = [0; 5];
+const MASS: Dimensions = [1, 0, 0, 0, 0];
+const LENGTH: Dimensions = [0, 1, 0, 0, 0];
+const TIME: Dimensions = [0, 0, 1, 0, 0];
+const AMOUNT: Dimensions = [0, 0, 0, 1, 0];
+const TEMPERATURE: Dimensions = [0, 0, 0, 0, 1];
+const VOLUME: Dimensions = [0, 3, 0, 0, 0];
+const CONCENTRATION: Dimensions = [0, -3, 0, 1, 0];
+const ENERGY: Dimensions = [1, 2, -2, 0, 0];
+const PRESSURE: Dimensions = [1, -1, -2, 0, 0];
+
+const CELSIUS: &[&str] = &["°C", "℃", "oC", "degC"];
+const CELSIUS_OFFSET: f64 = 273.15;
+
+/// Spelled-out unit names and the symbols they stand for, as in `0.774 grams`.
+const NAMES: &[(&str, &str)] = &[
+    ("gram", "g"),
+    ("metre", "m"),
+    ("meter", "m"),
+    ("second", "s"),
+    ("minute", "min"),
+    ("hour", "h"),
+    ("mole", "mol"),
+    ("kelvin", "K"),
+    ("litre", "L"),
+    ("liter", "L"),
+    ("molar", "M"),
+    ("joule", "J"),
+    ("pascal", "Pa"),
+];
+const NAME_PREFIXES: &[(&str, &str)] = &[
+    ("kilo", "k"),
+    ("centi", "c"),
+    ("deci", "d"),
+    ("milli", "m"),
+    ("micro", "µ"),
+    ("nano", "n"),
+];
+
+/// A unit of measurement, as a multiple of g, m, s, mol and K.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    factor: f64,
+    /// Only non-zero for a lone °C; in compound units such as J g⁻¹ °C⁻¹ a
+    /// degree Celsius is a temperature difference, the same as a kelvin.
+    offset: f64,
+    dimensions: Dimensions,
+}
+
+impl Unit {
+    /// Parses units such as `g`, `mol/L`, `M`, `kJ mol-1`, `J g⁻¹ K⁻¹`,
+    /// `mol dm^-3`, `J/(g °C)` or `moles per litre`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("unit is empty".to_string());
+        }
+
+        let mut unit = parse_product(&text.replace(['·', '⋅', '*'], " "))?;
+        if CELSIUS.contains(&text) {
+            unit.offset = CELSIUS_OFFSET;
+        }
+        Ok(unit)
+    }
+
+    /// Whether a value in this unit can be converted to `other`.
+    pub fn is_compatible(&self, other: &Unit) -> bool {
+        self.dimensions == other.dimensions
+    }
+
+    /// Converts `value` from this unit to `to`, or `None` if they measure different things.
+    pub fn convert(&self, value: f64, to: &Unit) -> Option<f64> {
+        self.is_compatible(to)
+            .then(|| (value * self.factor + self.offset - to.offset) / to.factor)
+    }
+
+    fn dimensionless() -> Self {
+        Self {
+            factor: 1.0,
+            offset: 0.0,
+            dimensions: DIMENSIONLESS,
+        }
+    }
+
+    fn multiply(&mut self, other: &Unit, exponent: i32) {
+        self.factor *= other.factor.powi(exponent);
+        for (dimension, other) in self.dimensions.iter_mut().zip(other.dimensions) {
+            *dimension += other * exponent;
+        }
+    }
+}
+
+/// A product of factors, where `/` divides by the next factor or bracketed group.
+fn parse_product(text: &str) -> Result<Unit, String> {
+    let mut unit = Unit::dimensionless();
+    let mut divide = false;
+    let mut rest = text.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        let is_per = rest.strip_prefix("per").is_some_and(|after| after.starts_with(char::is_whitespace));
+        let (factor, exponent, remaining) = if c == '/' || is_per {
+            if divide {
+                return Err(format!("unexpected '/' in unit '{}'", text));
+            }
+            divide = true;
+            rest = rest[if is_per { "per".len() } else { 1 }..].trim_start();
+            continue;
+        } else if c == '(' {
+            let close = rest.find(')').ok_or_else(|| format!("unclosed '(' in unit '{}'", text))?;
+            (parse_product(&rest[1..close])?, 1, &rest[close + 1..])
+        } else {
+            let end = rest.find(|c: char| c.is_whitespace() || c == '/' || c == '(').unwrap_or(rest.len());
+            let (factor, exponent) = parse_factor(&rest[..end])?;
+            (factor, exponent, &rest[end..])
+        };
+
+        unit.multiply(&factor, if divide { -exponent } else { exponent });
+        divide = false;
+        rest = remaining.trim_start();
+    }
+
+    if divide {
+        return Err(format!("unit '{}' ends with '/'", text));
+    }
+    Ok(unit)
+}
+
+/// One symbol with an optional prefix and exponent, such as `cm3`, `mol⁻¹` or `L^-1`.
+fn parse_factor(text: &str) -> Result<(Unit, i32), String> {
+    let symbol_end = text
+        .find(|c: char| c.is_ascii_digit() || matches!(c, '^' | '-' | '−' | '⁻' | '+' | '⁺' | '{') || superscript_digit(c).is_some())
+        .unwrap_or(text.len());
+    let (symbol, exponent) = text.split_at(symbol_end);
+    let unit = parse_symbol(symbol).ok_or_else(|| format!("unknown unit '{}'", symbol))?;
+
+    let exponent = exponent.trim_start_matches('^').trim_start_matches('{').trim_end_matches('}');
+    let exponent: String = exponent
+        .chars()
+        .map(|c| match c {
+            '−' | '⁻' => '-',
+            '⁺' => '+',
+            c => superscript_digit(c).unwrap_or(c),
+        })
+        .collect();
+    let exponent = match exponent.as_str() {
+        "" => 1,
+        other => other.parse().map_err(|_| format!("invalid exponent in unit '{}'", text))?,
+    };
+    Ok((unit, exponent))
+}
+
+fn parse_symbol(symbol: &str) -> Option<Unit> {
+    if let Some(symbol) = spelled_out(symbol) {
+        return parse_symbol(&symbol);
+    }
+    base_unit(symbol).or_else(|| {
+        let prefix = symbol.chars().next()?;
+        let scale = match prefix {
+            'k' => 1e3,
+            'M' => 1e6,
+            'c' => 1e-2,
+            'd' => 1e-1,
+            'm' => 1e-3,
+            'µ' | 'μ' | 'u' => 1e-6,
+            'n' => 1e-9,
+            _ => return None,
+        };
+        let base = &symbol[prefix.len_utf8()..];
+        if !matches!(base, "g" | "m" | "s" | "mol" | "L" | "l" | "M" | "J" | "Pa") {
+            return None;
+        }
+        let mut unit = base_unit(base)?;
+        unit.factor *= scale;
+        Some(unit)
+    })
+}
+
+/// The symbol for a unit name such as `grams` or `kilojoules`.
+fn spelled_out(name: &str) -> Option<String> {
+    let name = name.to_lowercase();
+    let singular = name.strip_suffix('s').unwrap_or(&name);
+    let (prefix, base) = NAME_PREFIXES
+        .iter()
+        .find_map(|(prefix, symbol)| singular.strip_prefix(prefix).map(|base| (*symbol, base)))
+        .unwrap_or(("", singular));
+    NAMES
+        .iter()
+        .find(|(unit, _)| *unit == base)
+        .map(|(_, symbol)| format!("{}{}", prefix, symbol))
+}
+
+fn base_unit(symbol: &str) -> Option<Unit> {
+    let (factor, dimensions) = match symbol {
+        "g" => (1.0, MASS),
+        "m" => (1.0, LENGTH),
+        "s" => (1.0, TIME),
+        "min" => (60.0, TIME),
+        "h" => (3600.0, TIME),
+        "mol" => (1.0, AMOUNT),
+        "K" => (1.0, TEMPERATURE),
+        s if CELSIUS.contains(&s) => (1.0, TEMPERATURE),
+        "L" | "l" => (1e-3, VOLUME),
+        "M" => (1e3, CONCENTRATION),
+        // SI energy and pressure units are defined on the kilogram
+        "J" => (1e3, ENERGY),
+        "Pa" => (1e3, PRESSURE),
+        "atm" => (101_325e3, PRESSURE),
+        "bar" => (1e8, PRESSURE),
+        "%" => (1e-2, DIMENSIONLESS),
+        "ppm" => (1e-6, DIMENSIONLESS),
+        _ => return None,
+    };
+    Some(Unit {
+        factor,
+        offset: 0.0,
+        dimensions,
+    })
+}
+
+fn superscript_digit(c: char) -> Option<char> {
+    match c {
+        '⁰' => Some('0'),
+        '¹' => Some('1'),
+        '²' => Some('2'),
+        '³' => Some('3'),
+        '⁴'..='⁹' => char::from_digit(c as u32 - '⁴' as u32 + 4, 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(value: f64, from: &str, to: &str) -> Option<f64> {
+        Unit::parse(from).unwrap().convert(value, &Unit::parse(to).unwrap())
+    }
+
+    fn close(a: Option<f64>, b: f64) -> bool {
+        a.is_some_and(|a| (a - b).abs() < 1e-9 * b.abs().max(1.0))
+    }
+
+    #[test]
+    fn converts_between_prefixes_and_notations() {
+        assert!(close(convert(774.0, "mg", "g"), 0.774));
+        assert!(close(convert(31.446, "kJ", "J"), 31_446.0));
+        assert!(close(convert(0.1, "M", "mol/L"), 0.1));
+        assert!(close(convert(100.0, "mmol/L", "M"), 0.1));
+        assert!(close(convert(0.1, "mol dm^-3", "mol L⁻¹"), 0.1));
+        assert!(close(convert(-5.294, "MJ/mol", "kJ mol-1"), -5294.0));
+        assert!(close(convert(4.18, "J g⁻¹ K⁻¹", "J/(g °C)"), 4.18));
+        assert!(close(convert(250.0, "cm3", "L"), 0.25));
+    }
+
+    #[test]
+    fn offsets_a_lone_celsius_temperature() {
+        assert!(close(convert(25.0, "°C", "K"), 298.15));
+        assert!(close(convert(1.0, "atm", "kPa"), 101.325));
+    }
+
+    #[test]
+    fn reads_spelled_out_units() {
+        assert!(close(convert(0.774, "grams", "g"), 0.774));
+        assert!(close(convert(31.4, "kilojoules", "J"), 31_400.0));
+        assert!(close(convert(0.1, "moles per litre", "M"), 0.1));
+        assert!(close(convert(250.0, "millilitres", "L"), 0.25));
+        assert!(close(convert(2.0, "minutes", "s"), 120.0));
+    }
+
+    #[test]
+    fn rejects_incompatible_and_unknown_units() {
+        assert_eq!(convert(1.0, "g", "mL"), None);
+        assert!(Unit::parse("mol/").is_err());
+        assert!(Unit::parse("apples").is_err());
+        assert!(Unit::parse("mol per").is_err());
+    }
+}
@@ -1,12 +1,57 @@
 use serde::{Deserialize, Serialize};
 use std::env;
 
+use crate::services::consensus::ConsensusPolicy;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub server_address: String,
     pub database_url: String,
     pub openai_api_key: String,
     pub gemini_api_key: String,
+    pub openai_api_url: String,
+    pub gemini_api_url: String,
+    pub openai_model: String,
+    pub gemini_model: String,
+    /// Grading providers in the order they are tried, e.g. `openai,gemini` or `mock`.
+    pub grading_providers: Vec<String>,
+    pub mock_fixtures_path: Option<String>,
+    /// Grade each response with every provider and combine the marks, instead of
+    /// falling back to the next provider only on error.
+    pub consensus_enabled: bool,
+    /// Gradings requested from each provider in consensus mode.
+    pub consensus_samples: usize,
+    pub consensus_policy: ConsensusPolicy,
+    /// Spread in marks between graders above which a response is flagged for review.
+    pub consensus_max_spread: f64,
+    /// Times a provider is asked to correct output that fails schema validation
+    /// before it counts as a failure.
+    pub grading_repair_attempts: usize,
+    /// Directory of versioned grading prompt templates.
+    pub prompts_path: String,
+    /// Prompt template version to grade with, a subdirectory of `prompts_path`.
+    pub grading_prompt_version: String,
+    /// How long a model's grading of a response is reused for identical
+    /// responses; 0 turns the cache off.
+    pub grading_cache_ttl_hours: u64,
+    /// Directory of exam definition files loaded at startup.
+    pub exams_path: String,
+    /// Number of concurrent grading workers, bounding simultaneous AI calls.
+    pub grading_workers: usize,
+    pub grading_max_attempts: u32,
+    pub grading_retry_base_seconds: u64,
+    /// How long a worker may hold a job before it is considered abandoned.
+    pub grading_lease_seconds: u64,
+    /// How long a login session remains valid.
+    pub session_ttl_hours: u64,
+    /// Admin account created at startup if it does not already exist.
+    pub admin_username: Option<String>,
+    pub admin_password: Option<String>,
+    /// Directory holding report template overrides and `branding.toml`.
+    pub templates_path: String,
+    /// TrueType fonts embedded in PDF reports.
+    pub pdf_font_path: String,
+    pub pdf_bold_font_path: String,
     pub storage_path: String,
     pub s3_endpoint: Option<String>,
     pub s3_bucket: Option<String>,
@@ -21,8 +66,36 @@ impl Config {
         Ok(Self {
             server_address: env::var("SERVER_ADDRESS").unwrap_or_else(|_| "0.0.0.0:8080".to_string()),
             database_url: env::var("DATABASE_URL").unwrap_or_else(|_| "memory".to_string()),
-            openai_api_key: env::var("OPENAI_API_KEY")?,
-            gemini_api_key: env::var("GEMINI_API_KEY")?,
+            openai_api_key: env::var("OPENAI_API_KEY").unwrap_or_default(),
+            gemini_api_key: env::var("GEMINI_API_KEY").unwrap_or_default(),
+            openai_api_url: env::var("OPENAI_API_URL").unwrap_or_else(|_| "https://api.openai.com/v1".to_string()),
+            gemini_api_url: env::var("GEMINI_API_URL")
+                .unwrap_or_else(|_| "https://generativelanguage.googleapis.com/v1beta".to_string()),
+            openai_model: env::var("OPENAI_MODEL").unwrap_or_else(|_| "o1-mini".to_string()),
+            gemini_model: env::var("GEMINI_MODEL").unwrap_or_else(|_| "gemini-2.0-flash-exp".to_string()),
+            grading_providers: parse_list(&env::var("GRADING_PROVIDERS").unwrap_or_else(|_| "openai,gemini".to_string())),
+            mock_fixtures_path: env::var("MOCK_FIXTURES_PATH").ok(),
+            consensus_enabled: parse_var("GRADING_CONSENSUS", false)?,
+            consensus_samples: parse_var("CONSENSUS_SAMPLES", 1)?,
+            consensus_policy: parse_var("CONSENSUS_POLICY", ConsensusPolicy::Median)?,
+            consensus_max_spread: parse_var("CONSENSUS_MAX_SPREAD", 1.0)?,
+            grading_repair_attempts: parse_var("GRADING_REPAIR_ATTEMPTS", 2)?,
+            prompts_path: env::var("PROMPTS_PATH").unwrap_or_else(|_| "prompts".to_string()),
+            grading_prompt_version: env::var("GRADING_PROMPT_VERSION").unwrap_or_else(|_| "v4".to_string()),
+            grading_cache_ttl_hours: parse_var("GRADING_CACHE_TTL_HOURS", 720)?,
+            exams_path: env::var("EXAMS_PATH").unwrap_or_else(|_| "exams".to_string()),
+            grading_workers: parse_var("GRADING_WORKERS", 4)?,
+            grading_max_attempts: parse_var("GRADING_MAX_ATTEMPTS", 5)?,
+            grading_retry_base_seconds: parse_var("GRADING_RETRY_BASE_SECONDS", 30)?,
+            grading_lease_seconds: parse_var("GRADING_LEASE_SECONDS", 600)?,
+            session_ttl_hours: parse_var("SESSION_TTL_HOURS", 12)?,
+            admin_username: env::var("ADMIN_USERNAME").ok(),
+            admin_password: env::var("ADMIN_PASSWORD").ok(),
+            templates_path: env::var("TEMPLATES_PATH").unwrap_or_else(|_| "templates".to_string()),
+            pdf_font_path: env::var("PDF_FONT_PATH")
+                .unwrap_or_else(|_| "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf".to_string()),
+            pdf_bold_font_path: env::var("PDF_BOLD_FONT_PATH")
+                .unwrap_or_else(|_| "/usr/share/fonts/truetype/dejavu/DejaVuSans-Bold.ttf".to_string()),
             storage_path: env::var("STORAGE_PATH").unwrap_or_else(|_| "./storage".to_string()),
             s3_endpoint: env::var("S3_ENDPOINT").ok(),
             s3_bucket: env::var("S3_BUCKET").ok(),
@@ -31,3 +104,25 @@ impl Config {
         })
     }
 }
+
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_lowercase())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn parse_var<T>(name: &str, default: T) -> anyhow::Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid value for {}: {}", name, e)),
+        Err(_) => Ok(default),
+    }
+}
@@ -0,0 +1,41 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Html,
+    Json,
+};
+
+use crate::{
+    handlers::export::{load_cohort, ExportQuery},
+    services::analytics::{analyse_multiple_choice, ItemAnalysis},
+    AppState,
+};
+
+/// Multiple-choice item statistics for an exam, optionally for one class.
+pub async fn get_item_analysis(
+    State(state): State<AppState>,
+    Path(exam_id): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Json<ItemAnalysis>, (StatusCode, String)> {
+    let (exam, entries) = load_cohort(&state, &exam_id, query.class()).await?;
+    let submissions: Vec<_> = entries.into_iter().map(|e| e.submission).collect();
+
+    Ok(Json(analyse_multiple_choice(&exam, &submissions)))
+}
+
+/// The item analysis as an HTML report for teachers.
+pub async fn view_item_analysis_report(
+    State(state): State<AppState>,
+    Path(exam_id): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    let (exam, entries) = load_cohort(&state, &exam_id, query.class()).await?;
+    let submissions: Vec<_> = entries.into_iter().map(|e| e.submission).collect();
+    let analysis = analyse_multiple_choice(&exam, &submissions);
+
+    state
+        .reports
+        .generate_item_analysis_report(&exam, &analysis, query.class())
+        .map(Html)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
@@ -0,0 +1,122 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::Json,
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    middleware::bearer_token,
+    models::{AuthUser, Role},
+    AppState,
+};
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+#[derive(Debug, Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoginResponse {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub user: AuthUser,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateUserRequest {
+    pub username: String,
+    pub display_name: Option<String>,
+    pub password: String,
+    pub role: Role,
+    pub class_name: Option<String>,
+}
+
+pub async fn login(
+    State(state): State<AppState>,
+    Json(request): Json<LoginRequest>,
+) -> Result<Json<LoginResponse>, StatusCode> {
+    let session = state
+        .auth
+        .login(request.username.trim(), &request.password)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    Ok(Json(LoginResponse {
+        token: session.token,
+        expires_at: session.expires_at,
+        user: session.user,
+    }))
+}
+
+pub async fn logout(State(state): State<AppState>, headers: HeaderMap) -> StatusCode {
+    let Some(token) = bearer_token(&headers) else {
+        return StatusCode::UNAUTHORIZED;
+    };
+
+    match state.auth.logout(token).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+pub async fn me(Extension(user): Extension<AuthUser>) -> Json<AuthUser> {
+    Json(user)
+}
+
+pub async fn list_users(State(state): State<AppState>) -> Result<Json<Vec<AuthUser>>, StatusCode> {
+    let users = state
+        .database
+        .list_users()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(users.into_iter().map(AuthUser::from).collect()))
+}
+
+/// Creates an account. Only admins can reach this route.
+pub async fn create_user(
+    State(state): State<AppState>,
+    Json(request): Json<CreateUserRequest>,
+) -> Result<Json<AuthUser>, (StatusCode, String)> {
+    let username = request.username.trim();
+    if username.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Username must not be empty".to_string()));
+    }
+    if request.password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Password must be at least {} characters", MIN_PASSWORD_LENGTH),
+        ));
+    }
+
+    let existing = state
+        .database
+        .get_user_by_username(username)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if existing.is_some() {
+        return Err((StatusCode::CONFLICT, format!("User '{}' already exists", username)));
+    }
+
+    let display_name = request
+        .display_name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .unwrap_or(username);
+
+    let user = state
+        .auth
+        .create_user(username, display_name, &request.password, request.role, request.class_name)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(user.into()))
+}
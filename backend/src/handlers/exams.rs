@@ -0,0 +1,136 @@
+use axum::{extract::{Path, State}, http::StatusCode, response::Json, Extension};
+use serde::Serialize;
+
+use crate::{
+    models::{exam::QuestionDefinition, AuthUser, ExamDefinition, QuestionKind},
+    AppState,
+};
+
+#[derive(Debug, Serialize)]
+pub struct ExamSummary {
+    pub id: String,
+    pub title: String,
+    pub subject: String,
+    pub question_count: usize,
+    pub total_marks: f64,
+}
+
+impl From<&ExamDefinition> for ExamSummary {
+    fn from(exam: &ExamDefinition) -> Self {
+        Self {
+            id: exam.id.clone(),
+            title: exam.title.clone(),
+            subject: exam.subject.clone(),
+            question_count: exam.questions().count(),
+            total_marks: exam.total_marks(),
+        }
+    }
+}
+
+/// An exam as students see it: the paper without answer keys, marking
+/// criteria, exemplars or guidelines.
+#[derive(Debug, Serialize)]
+pub struct StudentExam {
+    pub id: String,
+    pub title: String,
+    pub subject: String,
+    pub total_marks: f64,
+    pub sections: Vec<StudentSection>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StudentSection {
+    pub name: String,
+    pub instructions: Option<String>,
+    pub questions: Vec<StudentQuestion>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StudentQuestion {
+    pub id: String,
+    pub kind: QuestionKind,
+    pub text: String,
+    pub marks: f64,
+    pub parts: Vec<StudentQuestion>,
+}
+
+impl From<&ExamDefinition> for StudentExam {
+    fn from(exam: &ExamDefinition) -> Self {
+        Self {
+            id: exam.id.clone(),
+            title: exam.title.clone(),
+            subject: exam.subject.clone(),
+            total_marks: exam.total_marks(),
+            sections: exam
+                .sections
+                .iter()
+                .map(|section| StudentSection {
+                    name: section.name.clone(),
+                    instructions: section.instructions.clone(),
+                    questions: section.questions.iter().map(StudentQuestion::from).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&QuestionDefinition> for StudentQuestion {
+    fn from(question: &QuestionDefinition) -> Self {
+        Self {
+            id: question.id.clone(),
+            kind: question.kind,
+            text: question.text.clone(),
+            marks: question.marks,
+            parts: question.parts.iter().map(StudentQuestion::from).collect(),
+        }
+    }
+}
+
+/// The full definition for staff, or the student view of it.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum ExamView {
+    Full(ExamDefinition),
+    Student(StudentExam),
+}
+
+pub async fn list_exams(State(state): State<AppState>) -> Result<Json<Vec<ExamSummary>>, StatusCode> {
+    let exams = state
+        .database
+        .list_exams()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(exams.iter().map(ExamSummary::from).collect()))
+}
+
+/// Fetches an exam. Only staff see answer keys and marking criteria.
+pub async fn get_exam(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    Path(exam_id): Path<String>,
+) -> Result<Json<ExamView>, StatusCode> {
+    match state.database.get_exam(&exam_id).await {
+        Ok(Some(exam)) if user.is_staff() => Ok(Json(ExamView::Full(exam))),
+        Ok(Some(exam)) => Ok(Json(ExamView::Student(StudentExam::from(&exam)))),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// Creates or replaces an exam from a JSON definition.
+pub async fn upsert_exam(
+    State(state): State<AppState>,
+    Json(exam): Json<ExamDefinition>,
+) -> Result<Json<ExamSummary>, (StatusCode, String)> {
+    exam.validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    state
+        .database
+        .store_exam(&exam)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(ExamSummary::from(&exam)))
+}
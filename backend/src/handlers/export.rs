@@ -0,0 +1,140 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Seek},
+};
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    models::{AuthUser, ExamDefinition},
+    services::{
+        export::{file_name_safe, write_results_archive, ExportEntry},
+        pdf::PDFService,
+    },
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    /// Only include students in this class.
+    pub class: Option<String>,
+}
+
+impl ExportQuery {
+    pub fn class(&self) -> Option<&str> {
+        self.class.as_deref().map(str::trim).filter(|c| !c.is_empty())
+    }
+}
+
+/// Streams a ZIP of every student's results PDF for an exam, with a CSV gradebook.
+pub async fn export_exam_results(
+    State(state): State<AppState>,
+    Path(exam_id): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let class = query.class();
+    let (exam, entries) = load_cohort(&state, &exam_id, class).await?;
+
+    let pdf = state.pdf.as_ref().clone();
+    let archive_exam = exam.clone();
+    let (archive, length) = tokio::task::spawn_blocking(move || spool_results_archive(&archive_exam, &entries, &pdf))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let body = Body::from_stream(ReaderStream::new(tokio::fs::File::from_std(archive)));
+
+    let filename = match class {
+        Some(class) => format!("{}_{}_results.zip", file_name_safe(&exam.id), file_name_safe(class)),
+        None => format!("{}_results.zip", file_name_safe(&exam.id)),
+    };
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_LENGTH, length.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Writes the archive to an anonymous temporary file so a class's PDFs are
+/// never all held in memory, and returns it rewound with its length.
+fn spool_results_archive(
+    exam: &ExamDefinition,
+    entries: &[ExportEntry],
+    pdf: &PDFService,
+) -> anyhow::Result<(File, u64)> {
+    let path = std::env::temp_dir().join(format!("results-export-{}.zip", uuid::Uuid::new_v4()));
+    let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+    // The open handle keeps the data readable, and nothing is left behind if the export fails
+    std::fs::remove_file(&path)?;
+
+    let mut file = write_results_archive(BufWriter::new(file), exam, entries, pdf)?
+        .into_inner()
+        .map_err(|e| e.into_error())?;
+    let length = file.stream_position()?;
+    file.rewind()?;
+    Ok((file, length))
+}
+
+/// Loads an exam with its submissions and their students, keeping only
+/// students in `class` when one is given.
+pub(crate) async fn load_cohort(
+    state: &AppState,
+    exam_id: &str,
+    class: Option<&str>,
+) -> Result<(ExamDefinition, Vec<ExportEntry>), (StatusCode, String)> {
+    let exam = state
+        .database
+        .get_exam(exam_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, format!("Exam {} not found", exam_id)))?;
+
+    let submissions = state
+        .database
+        .list_submissions_for_exam(exam_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let students: HashMap<String, AuthUser> = state
+        .database
+        .list_users()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .into_iter()
+        .map(|user| (user.user_id.clone(), AuthUser::from(user)))
+        .collect();
+
+    let entries = submissions
+        .into_iter()
+        // A student may have submitted more than once, so every submission needs the record
+        .map(|submission| ExportEntry {
+            student: submission.student_id.as_ref().and_then(|id| students.get(id).cloned()),
+            submission,
+        })
+        .filter(|entry| match class {
+            Some(class) => entry
+                .student
+                .as_ref()
+                .and_then(|s| s.class_name.as_deref())
+                .is_some_and(|name| name.eq_ignore_ascii_case(class)),
+            None => true,
+        })
+        .collect();
+
+    Ok((exam, entries))
+}
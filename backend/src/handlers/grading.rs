@@ -1,14 +1,20 @@
 use axum::{
-    extract::{Path, State},
-    http::{header, StatusCode},
-    response::{IntoResponse, Response},
-    Json,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Response,
+    },
+    Extension, Json,
 };
-use serde::{Serialize, Deserialize};
+use futures_util::{stream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
 
 use crate::{
-    models::{GradingStatus, GradingResults},
-    services::pdf::PDFService,
+    middleware::bearer_token,
+    models::{AuthUser, GradingScope, GradingStatus, GradingResults, Submission},
+    services::progress::ProgressEvent,
     AppState,
 };
 
@@ -20,25 +26,28 @@ pub struct GradingStatusResponse {
 
 #[derive(Debug, Serialize)]
 pub struct ResultsResponse {
+    pub exam_id: String,
+    pub exam_title: Option<String>,
     pub results: Option<GradingResults>,
+    /// Keys of `results.question_feedback` in exam order.
+    pub question_order: Vec<String>,
     pub status: GradingStatus,
 }
 
 pub async fn get_grading_status(
     State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
     Path(submission_code): Path<String>,
 ) -> Result<Json<GradingStatusResponse>, StatusCode> {
-    let submission = state
-        .database
-        .get_submission(&submission_code)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::NOT_FOUND)?;
+    let submission = visible_submission(&state, &user, &submission_code).await?;
 
     let message = match &submission.grading_status {
         GradingStatus::Pending => "Grading is queued and will begin shortly".to_string(),
         GradingStatus::InProgress => "Your submission is currently being graded".to_string(),
         GradingStatus::Completed => "Grading completed successfully".to_string(),
+        GradingStatus::PartiallyCompleted => {
+            "Some questions could not be graded; their results are pending review".to_string()
+        }
         GradingStatus::Failed { error } => format!("Grading failed: {}", error),
     };
 
@@ -48,10 +57,11 @@ pub async fn get_grading_status(
     }))
 }
 
-pub async fn get_results(
+/// Queues a re-grade of only the questions that failed in a partially completed submission.
+pub async fn retry_failed_questions(
     State(state): State<AppState>,
     Path(submission_code): Path<String>,
-) -> Result<Json<ResultsResponse>, StatusCode> {
+) -> Result<Json<GradingStatusResponse>, StatusCode> {
     let submission = state
         .database
         .get_submission(&submission_code)
@@ -59,16 +69,31 @@ pub async fn get_results(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
 
-    Ok(Json(ResultsResponse {
-        results: submission.results,
-        status: submission.grading_status,
+    if !matches!(submission.grading_status, GradingStatus::PartiallyCompleted) {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    queue_grading(&state, &submission_code, submission.grading_status, GradingScope::FailedQuestions, false).await?;
+
+    Ok(Json(GradingStatusResponse {
+        status: GradingStatus::Pending,
+        message: "Failed questions have been queued for re-grading".to_string(),
     }))
 }
 
-pub async fn download_pdf(
+#[derive(Debug, Deserialize)]
+pub struct RegradeQuery {
+    /// Grade every response with the model again instead of reusing cached gradings.
+    #[serde(default)]
+    pub bypass_cache: bool,
+}
+
+/// Queues a full re-grade of a submission that has finished grading.
+pub async fn regrade_submission(
     State(state): State<AppState>,
     Path(submission_code): Path<String>,
-) -> Result<Response, StatusCode> {
+    Query(query): Query<RegradeQuery>,
+) -> Result<Json<GradingStatusResponse>, StatusCode> {
     let submission = state
         .database
         .get_submission(&submission_code)
@@ -76,17 +101,98 @@ pub async fn download_pdf(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
 
+    if matches!(submission.grading_status, GradingStatus::Pending | GradingStatus::InProgress) {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    queue_grading(&state, &submission_code, submission.grading_status, GradingScope::Full, query.bypass_cache).await?;
+
+    Ok(Json(GradingStatusResponse {
+        status: GradingStatus::Pending,
+        message: "The submission has been queued for re-grading".to_string(),
+    }))
+}
+
+/// Marks a finished submission as pending and queues it for grading. The status
+/// is stored before the job exists so a worker's `InProgress` is never
+/// overwritten, and restored if the job cannot be queued.
+async fn queue_grading(
+    state: &AppState,
+    submission_code: &str,
+    previous: GradingStatus,
+    scope: GradingScope,
+    bypass_cache: bool,
+) -> Result<(), StatusCode> {
+    state
+        .database
+        .update_grading_status(submission_code, GradingStatus::Pending)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if let Err(e) = state.grading_queue.enqueue(submission_code, scope, bypass_cache).await {
+        tracing::error!("Failed to queue grading for {}: {}", submission_code, e);
+        let _ = state.database.update_grading_status(submission_code, previous).await;
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    Ok(())
+}
+
+pub async fn get_results(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    Path(submission_code): Path<String>,
+) -> Result<Json<ResultsResponse>, StatusCode> {
+    let submission = visible_submission(&state, &user, &submission_code).await?;
+
+    let exam = state
+        .database
+        .get_exam(&submission.exam_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let question_order = match (&submission.results, &exam) {
+        (Some(results), Some(exam)) => results
+            .feedback_in_exam_order(exam)
+            .into_iter()
+            .map(|(key, _)| key.clone())
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Ok(Json(ResultsResponse {
+        exam_id: submission.exam_id,
+        exam_title: exam.map(|e| e.title),
+        results: submission.results,
+        question_order,
+        status: submission.grading_status,
+    }))
+}
+
+pub async fn download_pdf(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    Path(submission_code): Path<String>,
+) -> Result<Response, StatusCode> {
+    let submission = visible_submission(&state, &user, &submission_code).await?;
+
     if submission.results.is_none() {
         return Err(StatusCode::BAD_REQUEST);
     }
 
-    let pdf_service = PDFService::new();
-    let pdf_bytes = pdf_service
-        .generate_results_pdf(&submission)
+    let exam = state
+        .database
+        .get_exam(&submission.exam_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let pdf_bytes = state
+        .pdf
+        .generate_results_pdf(&submission, &exam)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let filename = format!("HSC_Chemistry_Results_{}.pdf", submission_code);
+    let filename = format!("{}_Results_{}.pdf", exam.id, submission_code);
 
     Ok((
         StatusCode::OK,
@@ -101,3 +207,109 @@ pub async fn download_pdf(
     )
         .into_response())
 }
+
+/// Renders the results report as HTML for viewing in the browser.
+pub async fn view_report(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    Path(submission_code): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let submission = visible_submission(&state, &user, &submission_code).await?;
+    let results = submission.results.as_ref().ok_or(StatusCode::BAD_REQUEST)?;
+
+    let exam = state
+        .database
+        .get_exam(&submission.exam_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    state
+        .reports
+        .generate_html_report(&submission, &exam, results)
+        .map(Html)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProgressQuery {
+    /// Session token, for clients such as `EventSource` that cannot set headers.
+    pub access_token: Option<String>,
+}
+
+/// Streams grading progress as Server-Sent Events until grading finishes.
+///
+/// Each event's data is a JSON object with an `event` type, the question it
+/// concerns, `completed` and `total` question counts and a readable `message`.
+/// A submission that has already been graded gets a single `finished` event.
+pub async fn stream_grading_progress(
+    State(state): State<AppState>,
+    Path(submission_code): Path<String>,
+    Query(query): Query<ProgressQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, StatusCode> {
+    let token = bearer_token(&headers)
+        .or(query.access_token.as_deref())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let user = state
+        .auth
+        .authenticate(token)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    // Subscribe before reading the status so a run finishing in between is not missed
+    let (latest, receiver) = state.progress.subscribe(&submission_code);
+    let submission = visible_submission(&state, &user, &submission_code).await?;
+
+    let initial = match (&submission.grading_status, latest) {
+        (_, Some(latest)) => Some(latest),
+        (GradingStatus::Pending | GradingStatus::InProgress, None) => None,
+        (status, None) => {
+            let graded = submission.results.as_ref().map_or(0, |results| {
+                results
+                    .question_feedback
+                    .values()
+                    .filter(|f| !f.question_id.is_empty() && f.multiple_choice.is_none())
+                    .count()
+            });
+            Some(ProgressEvent::finished(status, graded, graded))
+        }
+    };
+    let already_finished = initial.as_ref().is_some_and(|e| e.is_finished());
+
+    let updates = stream::unfold((receiver, already_finished), |(mut receiver, done)| async move {
+        if done {
+            return None;
+        }
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let done = event.is_finished();
+                    return Some((event, (receiver, done)));
+                }
+                // A slow client skips what it missed; the next event carries the counts
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    let events = stream::iter(initial)
+        .chain(updates)
+        .map(|event| Event::default().json_data(&event));
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Loads a submission the caller is allowed to see. Students get 404 for
+/// submissions that are not theirs, so codes cannot be probed.
+async fn visible_submission(state: &AppState, user: &AuthUser, submission_code: &str) -> Result<Submission, StatusCode> {
+    state
+        .database
+        .get_submission(submission_code)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .filter(|submission| user.can_view(submission))
+        .ok_or(StatusCode::NOT_FOUND)
+}
@@ -1,3 +1,8 @@
 pub mod submissions;
 pub mod grading;
 pub mod health;
+pub mod exams;
+pub mod moderation;
+pub mod analytics;
+pub mod auth;
+pub mod export;
@@ -0,0 +1,136 @@
+use axum::{extract::{Path, State}, http::StatusCode, response::Json, Extension};
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::{
+    models::{AuthUser, GradingResults, GradingStatus, MarkOverride, QuestionStatus},
+    services::grading::recompute_totals,
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct OverrideRequest {
+    pub score: f64,
+    /// Replacement feedback; the AI feedback is kept when omitted.
+    pub feedback: Option<String>,
+    pub reason: String,
+}
+
+/// Replaces the mark for one question and recomputes the submission totals.
+/// The override is attributed to the signed-in teacher.
+pub async fn override_question(
+    State(state): State<AppState>,
+    Extension(teacher): Extension<AuthUser>,
+    Path((submission_code, question_id)): Path<(String, String)>,
+    Json(request): Json<OverrideRequest>,
+) -> Result<Json<GradingResults>, (StatusCode, String)> {
+    if request.reason.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "A reason is required".to_string()));
+    }
+
+    let submission = state
+        .database
+        .get_submission(&submission_code)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, "Submission not found".to_string()))?;
+
+    let exam = state
+        .database
+        .get_exam(&submission.exam_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, format!("Exam {} not found", submission.exam_id)))?;
+
+    let mut results = submission
+        .results
+        .ok_or((StatusCode::CONFLICT, "Submission has not been graded yet".to_string()))?;
+
+    let feedback = results
+        .question_feedback
+        .values_mut()
+        .find(|f| !question_id.is_empty() && f.question_id == question_id)
+        .ok_or((StatusCode::NOT_FOUND, format!("No result for question {}", question_id)))?;
+
+    if !(0.0..=feedback.max_score).contains(&request.score) || request.score.fract() != 0.0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Score must be a whole number between 0 and {}", feedback.max_score),
+        ));
+    }
+
+    // The first override of a question holds the AI mark as its previous mark
+    let (original_score, original_feedback) = match results
+        .moderation_history
+        .iter()
+        .find(|r| r.question_id == question_id)
+    {
+        Some(first) => (
+            first.original_score.unwrap_or(first.previous_score),
+            first.original_feedback.clone().unwrap_or_else(|| first.previous_feedback.clone()),
+        ),
+        None => (feedback.score, feedback.feedback.clone()),
+    };
+
+    let record = MarkOverride {
+        question_id: question_id.clone(),
+        previous_score: feedback.score,
+        previous_feedback: feedback.feedback.clone(),
+        original_score: Some(original_score),
+        original_feedback: Some(original_feedback),
+        score: request.score,
+        feedback: request.feedback.clone(),
+        reason: request.reason.trim().to_string(),
+        teacher: teacher.username,
+        overridden_at: Utc::now(),
+    };
+
+    feedback.score = request.score;
+    if let Some(text) = request.feedback {
+        feedback.feedback = text;
+    }
+    // A teacher's mark settles any failed or flagged question
+    feedback.status = QuestionStatus::Graded;
+    feedback.moderated = true;
+
+    results.moderation_history.push(record);
+    recompute_totals(&exam, &mut results);
+
+    state
+        .database
+        .store_grading_results(&submission_code, &results)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Overriding the last failed question completes a partially graded submission
+    if matches!(submission.grading_status, GradingStatus::PartiallyCompleted)
+        && !results.question_feedback.values().any(|f| f.is_failed())
+    {
+        state
+            .database
+            .update_grading_status(&submission_code, GradingStatus::Completed)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    Ok(Json(results))
+}
+
+pub async fn get_moderation_history(
+    State(state): State<AppState>,
+    Path(submission_code): Path<String>,
+) -> Result<Json<Vec<MarkOverride>>, StatusCode> {
+    let submission = state
+        .database
+        .get_submission(&submission_code)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(
+        submission
+            .results
+            .map(|r| r.moderation_history)
+            .unwrap_or_default(),
+    ))
+}
@@ -1,29 +1,37 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::Json,
+    middleware::{from_fn, from_fn_with_state},
     routing::{get, post},
     Router,
 };
-use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tower_http::{cors::CorsLayer, services::ServeDir};
-use tracing::{info, warn};
+use tracing::info;
 
+mod chemistry;
 mod config;
 mod error;
 mod handlers;
+mod middleware;
 mod models;
 mod services;
 
 use config::Config;
-use handlers::{submissions, grading, health};
-use services::{database::DatabaseService, ai::AIService, storage::StorageService};
+use handlers::{analytics, auth, exams, export, submissions, grading, health, moderation};
+use models::ExamDefinition;
+use services::{
+    auth::AuthService, database::DatabaseService, ai::AIService, grading::GradingService, queue::GradingQueue,
+    pdf::PDFService, progress::ProgressHub, report::{Branding, ReportService}, storage::StorageService,
+};
 
 #[derive(Clone)]
 pub struct AppState {
     pub database: Arc<DatabaseService>,
+    pub auth: Arc<AuthService>,
     pub ai_service: Arc<AIService>,
+    pub grading_queue: Arc<GradingQueue>,
+    pub progress: Arc<ProgressHub>,
+    pub reports: Arc<ReportService>,
+    pub pdf: Arc<PDFService>,
     pub storage: Arc<StorageService>,
     pub config: Arc<Config>,
 }
@@ -38,29 +46,98 @@ async fn main() -> anyhow::Result<()> {
     
     // Initialize services
     let database = Arc::new(DatabaseService::new(&config.database_url).await?);
-    let mut ai_service = AIService::new(config.clone())?;
+    let mut ai_service = AIService::new(config.clone(), database.clone())?;
     ai_service.initialize().await?;
     let ai_service = Arc::new(ai_service);
     let storage = Arc::new(StorageService::new(config.clone()).await?);
 
     // Initialize database schema
     database.initialize_schema().await?;
+    database.delete_expired_cached_gradings().await?;
+
+    // Create the bootstrap admin account, if configured
+    let auth = Arc::new(AuthService::new(database.clone(), &config));
+    auth.ensure_admin(&config).await?;
+
+    // Load exam definitions from disk, replacing any stored copies
+    for exam in ExamDefinition::load_dir(&config.exams_path)? {
+        database.store_exam(&exam).await?;
+        info!("Loaded exam '{}' with {} questions", exam.id, exam.questions().count());
+    }
+
+    // Start the grading workers, picking up anything left over from a previous run
+    let progress = Arc::new(ProgressHub::new());
+    let grading_queue = Arc::new(GradingQueue::new(
+        database.clone(),
+        GradingService::new(database.clone(), ai_service.clone(), progress.clone()),
+        config.clone(),
+    ));
+    let requeued = grading_queue.recover().await?;
+    if requeued > 0 {
+        info!("Re-queued {} ungraded submissions", requeued);
+    }
+    grading_queue.start();
+
+    // Load report templates, school branding and PDF fonts
+    let branding = Branding::load(&config.templates_path)?;
+    let pdf = Arc::new(PDFService::new(&config, branding.clone()));
+    let reports = Arc::new(ReportService::new(&config.templates_path, branding)?);
 
     let app_state = AppState {
         database,
+        auth,
         ai_service,
+        grading_queue,
+        progress,
+        reports,
+        pdf,
         storage,
         config: config.clone(),
     };
 
-    // Build router
-    let app = Router::new()
-        .route("/health", get(health::health_check))
+    // Routes open to any signed-in user; handlers limit students to their own submissions
+    let user_routes = Router::new()
+        .route("/api/auth/me", get(auth::me))
+        .route("/api/auth/logout", post(auth::logout))
+        .route("/api/exams", get(exams::list_exams))
+        .route("/api/exams/:exam_id", get(exams::get_exam))
         .route("/api/submissions", post(submissions::submit_exam))
         .route("/api/submissions/:code", get(submissions::get_submission))
         .route("/api/grading/:code", get(grading::get_grading_status))
         .route("/api/results/:code", get(grading::get_results))
         .route("/api/results/:code/pdf", get(grading::download_pdf))
+        .route("/api/results/:code/report", get(grading::view_report));
+
+    let teacher_routes = Router::new()
+        .route("/api/exams", post(exams::upsert_exam))
+        .route("/api/exams/:exam_id/export", get(export::export_exam_results))
+        .route("/api/exams/:exam_id/analytics", get(analytics::get_item_analysis))
+        .route("/api/exams/:exam_id/analytics/report", get(analytics::view_item_analysis_report))
+        .route("/api/grading/:code/retry", post(grading::retry_failed_questions))
+        .route("/api/grading/:code/regrade", post(grading::regrade_submission))
+        .route("/api/results/:code/moderation", get(moderation::get_moderation_history))
+        .route(
+            "/api/results/:code/questions/:question_id/override",
+            post(moderation::override_question),
+        )
+        .route_layer(from_fn(middleware::require_teacher));
+
+    let admin_routes = Router::new()
+        .route("/api/users", get(auth::list_users).post(auth::create_user))
+        .route_layer(from_fn(middleware::require_admin));
+
+    let protected_routes = user_routes
+        .merge(teacher_routes)
+        .merge(admin_routes)
+        .route_layer(from_fn_with_state(app_state.clone(), middleware::require_auth));
+
+    // Build router
+    let app = Router::new()
+        .route("/health", get(health::health_check))
+        .route("/api/auth/login", post(auth::login))
+        // Authenticates itself so browser EventSource clients can pass the token in the URL
+        .route("/api/grading/:code/events", get(grading::stream_grading_progress))
+        .merge(protected_routes)
         .nest_service("/assets", ServeDir::new("assets"))
         .layer(CorsLayer::permissive())
         .with_state(app_state);
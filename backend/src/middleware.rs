@@ -0,0 +1,59 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{
+    models::{AuthUser, Role},
+    AppState,
+};
+
+/// Resolves the `Authorization: Bearer <token>` header to an [`AuthUser`] and
+/// attaches it to the request, rejecting requests without a valid session.
+pub async fn require_auth(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let token = bearer_token(request.headers()).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let user = state
+        .auth
+        .authenticate(token)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    request.extensions_mut().insert(user);
+    Ok(next.run(request).await)
+}
+
+/// Allows teachers and admins. Must run inside [`require_auth`].
+pub async fn require_teacher(request: Request, next: Next) -> Result<Response, StatusCode> {
+    match request.extensions().get::<AuthUser>() {
+        Some(user) if user.is_staff() => Ok(next.run(request).await),
+        Some(_) => Err(StatusCode::FORBIDDEN),
+        None => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+/// Allows admins only. Must run inside [`require_auth`].
+pub async fn require_admin(request: Request, next: Next) -> Result<Response, StatusCode> {
+    match request.extensions().get::<AuthUser>() {
+        Some(user) if user.role == Role::Admin => Ok(next.run(request).await),
+        Some(_) => Err(StatusCode::FORBIDDEN),
+        None => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
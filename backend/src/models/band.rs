@@ -0,0 +1,330 @@
+use serde::{Deserialize, Serialize};
+
+/// Which HSC reporting scale an exam is marked against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CourseType {
+    /// Bands 1-6 on a 100-mark scale.
+    #[default]
+    Standard,
+    /// Bands E1-E4 on a 50-mark scale.
+    Extension,
+}
+
+impl CourseType {
+    pub fn scale_max(self) -> f64 {
+        match self {
+            Self::Standard => 100.0,
+            Self::Extension => 50.0,
+        }
+    }
+
+    /// NESA's published band boundaries on the aligned scale, highest band first.
+    fn default_cutoffs(self) -> Vec<BandCutoff> {
+        let cutoffs: &[(&str, f64)] = match self {
+            Self::Standard => &[("6", 90.0), ("5", 80.0), ("4", 70.0), ("3", 60.0), ("2", 50.0), ("1", 0.0)],
+            Self::Extension => &[("E4", 45.0), ("E3", 35.0), ("E2", 25.0), ("E1", 0.0)],
+        };
+        cutoffs
+            .iter()
+            .map(|(band, min_mark)| BandCutoff {
+                band: band.to_string(),
+                min_mark: *min_mark,
+            })
+            .collect()
+    }
+}
+
+/// How an exam's raw totals are turned into HSC bands, from the `[bands]`
+/// table of its definition.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BandScale {
+    #[serde(default)]
+    pub course: CourseType,
+    /// Lowest aligned mark for each band. Defaults to NESA's boundaries.
+    #[serde(default)]
+    pub cutoffs: Vec<BandCutoff>,
+    /// Points of a piecewise-linear curve from raw total to aligned mark.
+    /// Without one, raw totals are scaled linearly onto the aligned scale.
+    #[serde(default)]
+    pub alignment: Vec<AlignmentPoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BandCutoff {
+    /// `6`-`1` for standard courses, `E4`-`E1` for extension courses.
+    pub band: String,
+    pub min_mark: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AlignmentPoint {
+    pub raw: f64,
+    pub aligned: f64,
+}
+
+/// The band awarded for a submission's total.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BandResult {
+    pub raw_mark: f64,
+    pub max_raw_mark: f64,
+    /// Mark on the course's reporting scale, after alignment.
+    pub aligned_mark: f64,
+    pub scale_max: f64,
+    pub band: String,
+}
+
+impl BandResult {
+    /// `Band 5` for standard courses, `E3` for extension courses.
+    pub fn display_name(&self) -> String {
+        if self.band.starts_with('E') {
+            self.band.clone()
+        } else {
+            format!("Band {}", self.band)
+        }
+    }
+
+    /// e.g. `Band 5 (84/100 aligned)`.
+    pub fn summary(&self) -> String {
+        format!("{} ({:.0}/{:.0} aligned)", self.display_name(), self.aligned_mark, self.scale_max)
+    }
+}
+
+impl BandScale {
+    fn cutoffs(&self) -> Vec<BandCutoff> {
+        let mut cutoffs = if self.cutoffs.is_empty() {
+            self.course.default_cutoffs()
+        } else {
+            self.cutoffs.clone()
+        };
+        cutoffs.sort_by(|a, b| b.min_mark.total_cmp(&a.min_mark));
+        cutoffs
+    }
+
+    /// Maps a raw total out of `max_raw_mark` onto the course's reporting scale.
+    pub fn align(&self, raw_mark: f64, max_raw_mark: f64) -> f64 {
+        let scale_max = self.course.scale_max();
+        let aligned = match (self.alignment.first(), self.alignment.last()) {
+            (Some(first), _) if raw_mark <= first.raw => first.aligned,
+            (_, Some(last)) if raw_mark >= last.raw => last.aligned,
+            (Some(_), Some(_)) => {
+                let upper = self.alignment.iter().position(|p| p.raw > raw_mark).unwrap_or(self.alignment.len() - 1);
+                let (low, high) = (self.alignment[upper - 1], self.alignment[upper]);
+                low.aligned + (raw_mark - low.raw) / (high.raw - low.raw) * (high.aligned - low.aligned)
+            }
+            _ if max_raw_mark > 0.0 => raw_mark / max_raw_mark * scale_max,
+            _ => 0.0,
+        };
+        aligned.clamp(0.0, scale_max)
+    }
+
+    /// Aligns a raw total and finds the highest band whose cut-off it reaches.
+    pub fn band_for(&self, raw_mark: f64, max_raw_mark: f64) -> BandResult {
+        let aligned_mark = self.align(raw_mark, max_raw_mark);
+        let cutoffs = self.cutoffs();
+        let band = cutoffs
+            .iter()
+            .find(|c| aligned_mark >= c.min_mark)
+            .or(cutoffs.last())
+            .map(|c| c.band.clone())
+            .unwrap_or_default();
+
+        BandResult {
+            raw_mark,
+            max_raw_mark,
+            aligned_mark,
+            scale_max: self.course.scale_max(),
+            band,
+        }
+    }
+
+    /// Reports problems with the cut-offs and alignment curve, given the exam's total marks.
+    pub fn validate(&self, total_marks: f64, errors: &mut Vec<String>) {
+        let scale_max = self.course.scale_max();
+        let valid_bands: &[&str] = match self.course {
+            CourseType::Standard => &["1", "2", "3", "4", "5", "6"],
+            CourseType::Extension => &["E1", "E2", "E3", "E4"],
+        };
+
+        for cutoff in &self.cutoffs {
+            if !valid_bands.contains(&cutoff.band.as_str()) {
+                errors.push(format!(
+                    "band '{}' is not valid for this course, expected one of {}",
+                    cutoff.band,
+                    valid_bands.join(", ")
+                ));
+            }
+            if !(0.0..=scale_max).contains(&cutoff.min_mark) {
+                errors.push(format!("cut-off for band {} must be between 0 and {}", cutoff.band, scale_max));
+            }
+        }
+
+        // Higher bands need strictly higher cut-offs; invalid bands are reported above
+        let mut by_band: Vec<&BandCutoff> = self
+            .cutoffs
+            .iter()
+            .filter(|c| valid_bands.contains(&c.band.as_str()))
+            .collect();
+        by_band.sort_by_key(|c| valid_bands.iter().position(|b| *b == c.band));
+        for pair in by_band.windows(2) {
+            if pair[1].min_mark <= pair[0].min_mark {
+                errors.push(format!(
+                    "cut-off for band {} must be higher than for band {}",
+                    pair[1].band, pair[0].band
+                ));
+            }
+        }
+
+        if self.alignment.len() == 1 {
+            errors.push("alignment curve needs at least two points".to_string());
+        }
+        for pair in self.alignment.windows(2) {
+            if pair[1].raw <= pair[0].raw || pair[1].aligned < pair[0].aligned {
+                errors.push(format!(
+                    "alignment points must rise with raw mark, but ({}, {}) follows ({}, {})",
+                    pair[1].raw, pair[1].aligned, pair[0].raw, pair[0].aligned
+                ));
+            }
+        }
+        for point in &self.alignment {
+            if point.raw < 0.0 || point.raw > total_marks {
+                errors.push(format!("alignment raw mark {} is outside 0-{}", point.raw, total_marks));
+            }
+            if !(0.0..=scale_max).contains(&point.aligned) {
+                errors.push(format!("aligned mark {} is outside 0-{}", point.aligned, scale_max));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scale(toml: &str) -> BandScale {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn errors(scale: &BandScale, total_marks: f64) -> Vec<String> {
+        let mut errors = Vec::new();
+        scale.validate(total_marks, &mut errors);
+        errors
+    }
+
+    #[test]
+    fn awards_a_band_exactly_on_each_cut_off() {
+        let standard = BandScale::default();
+        for (raw, band) in [(90.0, "6"), (80.0, "5"), (70.0, "4"), (60.0, "3"), (50.0, "2"), (0.0, "1")] {
+            assert_eq!(standard.band_for(raw, 100.0).band, band, "{}", raw);
+        }
+        assert_eq!(standard.band_for(89.9, 100.0).band, "5");
+        assert_eq!(standard.band_for(49.9, 100.0).band, "1");
+
+        let extension = scale("course = \"extension\"");
+        for (raw, band) in [(45.0, "E4"), (35.0, "E3"), (25.0, "E2"), (24.9, "E1")] {
+            assert_eq!(extension.band_for(raw, 50.0).band, band, "{}", raw);
+        }
+        // Raw totals are scaled onto the 50-mark scale
+        let result = extension.band_for(22.5, 25.0);
+        assert_eq!((result.aligned_mark, result.band.as_str()), (45.0, "E4"));
+        assert_eq!(result.summary(), "E4 (45/50 aligned)");
+    }
+
+    #[test]
+    fn clamps_totals_outside_the_scale() {
+        let standard = BandScale::default();
+        let above = standard.band_for(110.0, 100.0);
+        assert_eq!((above.aligned_mark, above.band.as_str()), (100.0, "6"));
+        let below = standard.band_for(-5.0, 100.0);
+        assert_eq!((below.aligned_mark, below.band.as_str()), (0.0, "1"));
+        assert_eq!(standard.band_for(0.0, 0.0).aligned_mark, 0.0);
+    }
+
+    #[test]
+    fn uses_custom_cut_offs_in_any_order() {
+        let custom = scale(
+            r#"
+[[cutoffs]]
+band = "1"
+min_mark = 0
+[[cutoffs]]
+band = "6"
+min_mark = 85
+[[cutoffs]]
+band = "3"
+min_mark = 40
+"#,
+        );
+        assert_eq!(custom.band_for(85.0, 100.0).band, "6");
+        assert_eq!(custom.band_for(84.0, 100.0).band, "3");
+        assert_eq!(custom.band_for(39.0, 100.0).band, "1");
+        assert_eq!(custom.band_for(85.0, 100.0).display_name(), "Band 6");
+    }
+
+    #[test]
+    fn interpolates_between_alignment_points() {
+        let curve = scale(
+            r#"
+alignment = [
+    { raw = 10, aligned = 20 },
+    { raw = 40, aligned = 60 },
+    { raw = 80, aligned = 95 },
+    { raw = 100, aligned = 100 },
+]
+"#,
+        );
+        assert_eq!(curve.align(5.0, 100.0), 20.0);
+        assert_eq!(curve.align(25.0, 100.0), 40.0);
+        assert_eq!(curve.align(40.0, 100.0), 60.0);
+        assert_eq!(curve.align(60.0, 100.0), 77.5);
+        assert_eq!(curve.align(90.0, 100.0), 97.5);
+        assert_eq!(curve.align(120.0, 100.0), 100.0);
+
+        let result = curve.band_for(60.0, 100.0);
+        assert_eq!((result.aligned_mark, result.band.as_str()), (77.5, "4"));
+        assert!(errors(&curve, 100.0).is_empty());
+    }
+
+    #[test]
+    fn rejects_tables_that_do_not_rise() {
+        let cutoffs = scale(
+            r#"
+[[cutoffs]]
+band = "5"
+min_mark = 70
+[[cutoffs]]
+band = "4"
+min_mark = 75
+[[cutoffs]]
+band = "7"
+min_mark = 101
+"#,
+        );
+        assert_eq!(
+            errors(&cutoffs, 100.0),
+            [
+                "band '7' is not valid for this course, expected one of 1, 2, 3, 4, 5, 6",
+                "cut-off for band 7 must be between 0 and 100",
+                "cut-off for band 5 must be higher than for band 4",
+            ]
+        );
+
+        let curve = scale(
+            "alignment = [{ raw = 0, aligned = 0 }, { raw = 60, aligned = 80 }, { raw = 50, aligned = 70 }]",
+        );
+        assert_eq!(
+            errors(&curve, 100.0),
+            ["alignment points must rise with raw mark, but (50, 70) follows (60, 80)"]
+        );
+
+        let single = scale("course = \"extension\"\nalignment = [{ raw = 120, aligned = 55 }]");
+        assert_eq!(
+            errors(&single, 100.0),
+            [
+                "alignment curve needs at least two points",
+                "alignment raw mark 120 is outside 0-100",
+                "aligned mark 55 is outside 0-50",
+            ]
+        );
+    }
+}
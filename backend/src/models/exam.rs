@@ -0,0 +1,481 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, path::Path};
+
+use super::band::{BandResult, BandScale};
+use crate::chemistry::{
+    nomenclature::{self, IsomerKind},
+    units::Unit,
+};
+
+/// A complete examination paper, loaded from a TOML definition file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExamDefinition {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub subject: String,
+    /// Subject guidelines used as the system prompt when grading this exam.
+    #[serde(default)]
+    pub marking_guidelines: Option<String>,
+    /// Guidelines file, relative to the definition, read into `marking_guidelines` on load.
+    #[serde(default, skip_serializing)]
+    pub marking_guidelines_file: Option<String>,
+    pub sections: Vec<ExamSection>,
+    /// Band cut-offs and alignment; NESA's standard bands scaled linearly if omitted.
+    #[serde(default)]
+    pub bands: BandScale,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExamSection {
+    pub name: String,
+    #[serde(default)]
+    pub instructions: Option<String>,
+    pub questions: Vec<QuestionDefinition>,
+}
+
+/// A question, or a question split into separately marked parts such as 25a and 25b.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestionDefinition {
+    pub id: String,
+    #[serde(default)]
+    pub kind: QuestionKind,
+    /// The question text, or the shared stem when the question has parts.
+    #[serde(default)]
+    pub text: String,
+    /// Left unset on questions with parts, which are worth the sum of their parts.
+    #[serde(default)]
+    pub marks: f64,
+    /// Correct option for multiple-choice questions.
+    #[serde(default)]
+    pub answer: Option<String>,
+    /// Why the correct option is right, shown to students after grading.
+    #[serde(default)]
+    pub explanation: Option<String>,
+    /// Syllabus dot point the question assesses.
+    #[serde(default)]
+    pub syllabus_point: Option<String>,
+    #[serde(default)]
+    pub criteria: Vec<MarkingCriterion>,
+    /// Model answers included in the grading prompt for comparison.
+    #[serde(default)]
+    pub exemplars: Vec<String>,
+    /// Expected result of a calculation, checked without the model.
+    #[serde(default)]
+    pub numeric_answer: Option<NumericAnswer>,
+    /// Isomers the student is asked to name, marked without the model when the names are clear.
+    #[serde(default)]
+    pub isomers: Option<IsomerAnswer>,
+    /// Accepted answers to each field of a short-answer question.
+    #[serde(default)]
+    pub short_answer: Option<ShortAnswerKey>,
+    /// Sub-questions, each with its own id (e.g. `q25a`), marks and criteria.
+    #[serde(default)]
+    pub parts: Vec<QuestionDefinition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuestionKind {
+    MultipleChoice,
+    #[default]
+    ExtendedResponse,
+    /// Structured answers such as names in labelled fields, marked against
+    /// accepted answers and only sent to the model when none apply.
+    ShortAnswer,
+}
+
+/// The final answer of a calculation question, e.g. `0.774 g` to 3 significant figures.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NumericAnswer {
+    pub value: f64,
+    /// e.g. `g`, `mol/L` or `kJ mol-1`. Answers in convertible units, such as
+    /// `M` for `mol/L`, are accepted.
+    #[serde(default)]
+    pub unit: Option<String>,
+    /// Largest accepted difference from `value`, in `unit`. Defaults to 1% of `value`.
+    #[serde(default)]
+    pub tolerance: Option<f64>,
+    #[serde(default)]
+    pub significant_figures: Option<usize>,
+}
+
+/// Pairs of isomers a question asks for, e.g. one pair of chain isomers of C4H8O.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IsomerAnswer {
+    /// Molecular formula every named compound must have.
+    pub formula: String,
+    /// The compounds students must choose from; any isomer is accepted if empty.
+    #[serde(default)]
+    pub compounds: Vec<String>,
+    pub pairs: Vec<IsomerPair>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IsomerPair {
+    pub kind: IsomerKind,
+    pub marks: f64,
+}
+
+/// Accepted answers for a short-answer question and how answers are compared.
+/// Case, hyphens and spacing are ignored unless marked significant.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShortAnswerKey {
+    #[serde(default)]
+    pub case_sensitive: bool,
+    #[serde(default)]
+    pub hyphens_significant: bool,
+    #[serde(default)]
+    pub spacing_significant: bool,
+    /// Groups of answers that mean the same, e.g. `["butan-2-one", "butanone"]`.
+    #[serde(default)]
+    pub synonyms: Vec<Vec<String>>,
+    pub rules: Vec<AnswerRule>,
+}
+
+/// Marks for one field, or for several fields answered together such as a
+/// pair of compounds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnswerRule {
+    /// Shown against the rule in results, e.g. "Functional group isomers".
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Response fields the rule reads; a plain-text response is its only field.
+    pub fields: Vec<String>,
+    pub marks: f64,
+    /// Whether the fields may be answered in any order, as for a pair.
+    #[serde(default)]
+    pub any_order: bool,
+    pub accepted: Vec<ExpectedAnswer>,
+    /// Known wrong answers, marked zero rather than sent to the model.
+    #[serde(default)]
+    pub rejected: Vec<ExpectedAnswer>,
+}
+
+/// One answer per field of a rule: a string for a single field, or a list.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ExpectedAnswer {
+    Single(String),
+    Fields(Vec<String>),
+}
+
+impl ExpectedAnswer {
+    pub fn values(&self) -> &[String] {
+        match self {
+            Self::Single(answer) => std::slice::from_ref(answer),
+            Self::Fields(answers) => answers,
+        }
+    }
+}
+
+impl ShortAnswerKey {
+    fn validate(&self, question_id: &str, marks: f64, errors: &mut Vec<String>) {
+        for rule in &self.rules {
+            let name = rule.description.clone().unwrap_or_else(|| rule.fields.join(", "));
+            if rule.fields.is_empty() {
+                errors.push(format!("an answer rule on question '{}' reads no fields", question_id));
+            }
+            if rule.accepted.is_empty() {
+                errors.push(format!("answer rule '{}' on question '{}' accepts nothing", name, question_id));
+            }
+            if rule.marks < 0.0 || rule.marks.fract() != 0.0 {
+                errors.push(format!("answer rule '{}' on question '{}' must award a whole number of marks", name, question_id));
+            }
+            if let Some(answer) = rule
+                .accepted
+                .iter()
+                .chain(&rule.rejected)
+                .find(|answer| answer.values().len() != rule.fields.len())
+            {
+                errors.push(format!(
+                    "answer {:?} for rule '{}' on question '{}' needs one value per field",
+                    answer.values(),
+                    name,
+                    question_id
+                ));
+            }
+        }
+
+        let total: f64 = self.rules.iter().map(|rule| rule.marks).sum();
+        if self.rules.is_empty() || total != marks {
+            errors.push(format!(
+                "answer rules on question '{}' award {} marks but the question is worth {}",
+                question_id, total, marks
+            ));
+        }
+    }
+}
+
+/// A single band of a NESA-style marking guideline, e.g. "3 marks: ...".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarkingCriterion {
+    pub marks: f64,
+    pub description: String,
+}
+
+const MULTIPLE_CHOICE_OPTIONS: &[&str] = &["A", "B", "C", "D"];
+
+impl ExamDefinition {
+    /// Reads and validates an exam definition from a TOML file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read exam definition {}", path.display()))?;
+        let mut exam: Self = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse exam definition {}", path.display()))?;
+
+        if let Some(guidelines_file) = exam.marking_guidelines_file.take() {
+            let guidelines_path = path.parent().unwrap_or_else(|| Path::new(".")).join(guidelines_file);
+            exam.marking_guidelines = Some(std::fs::read_to_string(&guidelines_path).with_context(|| {
+                format!("Failed to read marking guidelines {}", guidelines_path.display())
+            })?);
+        }
+
+        exam.validate()
+            .with_context(|| format!("Invalid exam definition {}", path.display()))?;
+
+        Ok(exam)
+    }
+
+    /// Loads every `*.toml` definition in a directory, sorted by file name.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let dir = dir.as_ref();
+        let mut paths = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read exam directory {}", dir.display()))?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "toml"));
+        paths.sort();
+
+        let exams = paths.iter().map(Self::load).collect::<Result<Vec<_>>>()?;
+
+        let mut seen_ids = HashSet::new();
+        for exam in &exams {
+            if !seen_ids.insert(exam.id.as_str()) {
+                anyhow::bail!("Exam id '{}' is defined more than once in {}", exam.id, dir.display());
+            }
+        }
+
+        Ok(exams)
+    }
+
+    /// Checks the definition for structural problems, reporting all of them at once.
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+        let mut seen_ids = HashSet::new();
+
+        if self.id.trim().is_empty() {
+            errors.push("exam id must not be empty".to_string());
+        }
+        if self.title.trim().is_empty() {
+            errors.push("exam title must not be empty".to_string());
+        }
+        if self.sections.is_empty() {
+            errors.push("exam must have at least one section".to_string());
+        }
+
+        for section in &self.sections {
+            if section.questions.is_empty() {
+                errors.push(format!("section '{}' has no questions", section.name));
+            }
+
+            for question in &section.questions {
+                if !seen_ids.insert(question.id.as_str()) {
+                    errors.push(format!("duplicate question id '{}'", question.id));
+                }
+                for part in &question.parts {
+                    if !seen_ids.insert(part.id.as_str()) {
+                        errors.push(format!("duplicate question id '{}'", part.id));
+                    }
+                }
+                question.validate(&mut errors);
+            }
+        }
+
+        self.bands.validate(self.total_marks(), &mut errors);
+
+        if !errors.is_empty() {
+            anyhow::bail!(errors.join("; "));
+        }
+
+        Ok(())
+    }
+
+    /// Every separately marked question in exam order, with parts in place of
+    /// the questions they belong to.
+    pub fn questions(&self) -> impl Iterator<Item = &QuestionDefinition> {
+        self.sections.iter().flat_map(|section| section.questions())
+    }
+
+    /// The question a part belongs to, if `part_id` is a part.
+    pub fn parent_of(&self, part_id: &str) -> Option<&QuestionDefinition> {
+        self.sections
+            .iter()
+            .flat_map(|section| section.questions.iter())
+            .find(|q| q.parts.iter().any(|part| part.id == part_id))
+    }
+
+    pub fn total_marks(&self) -> f64 {
+        self.questions().map(|q| q.marks).sum()
+    }
+
+    pub fn band_for(&self, raw_mark: f64, max_raw_mark: f64) -> BandResult {
+        self.bands.band_for(raw_mark, max_raw_mark)
+    }
+}
+
+impl ExamSection {
+    /// Separately marked questions in this section; see `ExamDefinition::questions`.
+    pub fn questions(&self) -> impl Iterator<Item = &QuestionDefinition> {
+        self.questions.iter().flat_map(|q| q.marked_parts())
+    }
+
+    pub fn total_marks(&self) -> f64 {
+        self.questions().map(|q| q.marks).sum()
+    }
+}
+
+impl QuestionDefinition {
+    /// The parts of this question, or the question itself if it has none.
+    pub fn marked_parts(&self) -> &[QuestionDefinition] {
+        if self.parts.is_empty() {
+            std::slice::from_ref(self)
+        } else {
+            &self.parts
+        }
+    }
+
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.id.trim().is_empty() {
+            errors.push("question id must not be empty".to_string());
+            return;
+        }
+        if !self.parts.is_empty() {
+            self.validate_parts(errors);
+            return;
+        }
+        self.validate_marking(errors);
+    }
+
+    fn validate_parts(&self, errors: &mut Vec<String>) {
+        let part_marks: f64 = self.parts.iter().map(|p| p.marks).sum();
+        if self.marks != 0.0 && self.marks != part_marks {
+            errors.push(format!(
+                "question '{}' is worth {} marks but its parts add up to {}",
+                self.id, self.marks, part_marks
+            ));
+        }
+        if self.answer.is_some() || !self.criteria.is_empty() {
+            errors.push(format!("question '{}' has parts, so its answer and criteria belong on the parts", self.id));
+        }
+
+        for part in &self.parts {
+            if !part.id.starts_with(&self.id) || part.id == self.id {
+                errors.push(format!("part '{}' of question '{}' must have an id like '{}a'", part.id, self.id, self.id));
+            }
+            if !part.parts.is_empty() {
+                errors.push(format!("part '{}' cannot have parts of its own", part.id));
+            }
+            part.validate_marking(errors);
+        }
+    }
+
+    fn validate_marking(&self, errors: &mut Vec<String>) {
+        if self.id.trim().is_empty() {
+            errors.push("question id must not be empty".to_string());
+        }
+        if self.marks <= 0.0 || self.marks.fract() != 0.0 {
+            errors.push(format!("question '{}' must be worth a positive whole number of marks", self.id));
+        }
+
+        match self.kind {
+            QuestionKind::MultipleChoice => match &self.answer {
+                Some(answer) if MULTIPLE_CHOICE_OPTIONS.contains(&answer.as_str()) => {}
+                Some(answer) => errors.push(format!(
+                    "question '{}' has answer '{}', expected one of {}",
+                    self.id,
+                    answer,
+                    MULTIPLE_CHOICE_OPTIONS.join(", ")
+                )),
+                None => errors.push(format!("multiple choice question '{}' has no answer", self.id)),
+            },
+            QuestionKind::ExtendedResponse | QuestionKind::ShortAnswer => {
+                if self.answer.is_some() {
+                    errors.push(format!("question '{}' is not multiple choice, so it must not set an answer", self.id));
+                }
+            }
+        }
+
+        match (&self.short_answer, self.kind) {
+            (Some(key), QuestionKind::ShortAnswer) => key.validate(&self.id, self.marks, errors),
+            (None, QuestionKind::ShortAnswer) => {
+                errors.push(format!("short answer question '{}' has no accepted answers", self.id))
+            }
+            (Some(_), _) => errors.push(format!("question '{}' has accepted answers but is not a short answer", self.id)),
+            (None, _) => {}
+        }
+
+        if let Some(numeric) = &self.numeric_answer {
+            if self.kind == QuestionKind::MultipleChoice {
+                errors.push(format!("question '{}' is multiple choice, so it cannot have a numeric answer", self.id));
+            }
+            if !numeric.value.is_finite() {
+                errors.push(format!("numeric answer to question '{}' must be a number", self.id));
+            }
+            if let Some(Err(e)) = numeric.unit.as_deref().map(Unit::parse) {
+                errors.push(format!("numeric answer to question '{}' has an invalid unit: {}", self.id, e));
+            }
+            if numeric.tolerance.is_some_and(|t| t.is_nan() || t < 0.0) {
+                errors.push(format!("numeric answer tolerance on question '{}' must not be negative", self.id));
+            }
+            if numeric.significant_figures == Some(0) {
+                errors.push(format!("numeric answer to question '{}' must require at least one significant figure", self.id));
+            }
+        }
+
+        if let Some(isomers) = &self.isomers {
+            if self.kind == QuestionKind::MultipleChoice {
+                errors.push(format!("question '{}' is multiple choice, so it cannot ask for isomers", self.id));
+            }
+            match nomenclature::hill_formula(&isomers.formula) {
+                Some(formula) => {
+                    for name in &isomers.compounds {
+                        match nomenclature::parse_name(name) {
+                            Ok(compound) if compound.formula() != formula => errors.push(format!(
+                                "isomer '{}' on question '{}' is {}, not {}",
+                                name,
+                                self.id,
+                                compound.formula(),
+                                formula
+                            )),
+                            Ok(_) => {}
+                            Err(e) => errors.push(format!("isomer '{}' on question '{}' is not understood: {}", name, self.id, e)),
+                        }
+                    }
+                }
+                None => errors.push(format!(
+                    "isomers on question '{}' have an invalid formula '{}'",
+                    self.id, isomers.formula
+                )),
+            }
+            let total: f64 = isomers.pairs.iter().map(|pair| pair.marks).sum();
+            if isomers.pairs.is_empty() || total != self.marks {
+                errors.push(format!(
+                    "isomer pairs on question '{}' award {} marks but the question is worth {}",
+                    self.id, total, self.marks
+                ));
+            }
+        }
+
+        for criterion in &self.criteria {
+            if criterion.marks < 0.0 || criterion.marks > self.marks {
+                errors.push(format!(
+                    "criterion '{}' on question '{}' awards {} marks, outside 0-{}",
+                    criterion.description, self.id, criterion.marks, self.marks
+                ));
+            }
+        }
+    }
+}
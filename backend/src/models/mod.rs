@@ -3,10 +3,25 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 
+use crate::chemistry::{EquationCheck, IsomerCheck, NumericCheck};
+
+pub mod band;
+pub mod exam;
+pub mod user;
+
+pub use band::BandResult;
+pub use exam::{ExamDefinition, QuestionKind};
+pub use user::{AuthUser, Role, User};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Submission {
     pub id: Uuid,
     pub submission_code: String,
+    #[serde(default)]
+    pub exam_id: String,
+    /// Account that submitted the exam; students can only see their own submissions.
+    #[serde(default)]
+    pub student_id: Option<String>,
     pub responses: ExamResponses,
     pub submitted_at: DateTime<Utc>,
     pub grading_status: GradingStatus,
@@ -25,9 +40,46 @@ pub enum GradingStatus {
     Pending,
     InProgress,
     Completed,
+    /// Some questions could not be graded; their feedback has status `Failed`.
+    PartiallyCompleted,
     Failed { error: String },
 }
 
+/// A unit of grading work persisted in the `grading_jobs` table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GradingJob {
+    pub job_id: String,
+    pub submission_code: String,
+    pub status: JobStatus,
+    #[serde(default)]
+    pub scope: GradingScope,
+    /// Grade with the model even when a cached grading of the response exists.
+    #[serde(default)]
+    pub bypass_cache: bool,
+    pub attempts: u32,
+    pub max_attempts: u32,
+    pub available_at: DateTime<Utc>,
+    pub lease_expires_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Which questions a grading job should (re)grade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GradingScope {
+    #[default]
+    Full,
+    FailedQuestions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobStatus {
+    Queued,
+    Leased,
+    Completed,
+    Failed,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GradingResults {
     pub total_score: f64,
@@ -37,6 +89,34 @@ pub struct GradingResults {
     pub overall_feedback: String,
     pub ai_provider_used: String,
     pub graded_at: DateTime<Utc>,
+    /// HSC band for the total, from the exam's cut-offs and alignment curve.
+    #[serde(default)]
+    pub band: Option<BandResult>,
+    /// Version of the grading prompt templates used for AI-graded questions.
+    #[serde(default)]
+    pub prompt_version: Option<String>,
+    /// Every teacher override applied to these results, oldest first.
+    #[serde(default)]
+    pub moderation_history: Vec<MarkOverride>,
+}
+
+impl GradingResults {
+    /// Question feedback in the order the exam defines its questions, with
+    /// aggregate entries (such as the multiple-choice summary in older results) first.
+    pub fn feedback_in_exam_order(&self, exam: &ExamDefinition) -> Vec<(&String, &QuestionFeedback)> {
+        let position = |feedback: &QuestionFeedback| {
+            if feedback.question_id.is_empty() {
+                return 0;
+            }
+            exam.questions()
+                .position(|q| q.id == feedback.question_id)
+                .map_or(usize::MAX, |i| i + 1)
+        };
+
+        let mut entries: Vec<_> = self.question_feedback.iter().collect();
+        entries.sort_by(|(a_key, a), (b_key, b)| position(a).cmp(&position(b)).then_with(|| a_key.cmp(b_key)));
+        entries
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,20 +126,165 @@ pub struct SectionScore {
     pub feedback: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct QuestionFeedback {
+    /// Exam question id, e.g. `q22`; empty for aggregate entries.
+    #[serde(default)]
+    pub question_id: String,
+    #[serde(default)]
+    pub status: QuestionStatus,
     pub score: f64,
     pub max_score: f64,
     pub feedback: String,
     pub strengths: Vec<String>,
     pub improvements: Vec<String>,
     pub band_estimate: Option<String>,
+    /// Per-criterion verdicts against the question's marking criteria.
+    #[serde(default)]
+    pub criteria: Vec<CriterionResult>,
+    /// Corrections made to the model's output before it was accepted.
+    #[serde(default)]
+    pub validation_warnings: Vec<String>,
+    /// Set once a teacher has overridden the AI mark; see `GradingResults::moderation_history`.
+    #[serde(default)]
+    pub moderated: bool,
+    /// Every grader's mark when the response was graded in consensus mode.
+    #[serde(default)]
+    pub individual_marks: Vec<GraderMark>,
+    /// The student's choice and the key, for multiple-choice questions.
+    #[serde(default)]
+    pub multiple_choice: Option<MultipleChoiceResult>,
+    /// Balance and state-symbol checks of the equations found in the response.
+    #[serde(default)]
+    pub equation_checks: Vec<EquationCheck>,
+    /// Value, unit and significant-figure check of a calculation's final answer.
+    #[serde(default)]
+    pub numeric_check: Option<NumericCheck>,
+    /// The isomer pairs named in the response, for questions that ask for them.
+    #[serde(default)]
+    pub isomer_check: Option<IsomerCheck>,
 }
 
-// Multiple choice answer key from the HSC paper
-pub const ANSWER_KEY: &[(&str, &str)] = &[
-    ("q1", "D"), ("q2", "C"), ("q3", "B"), ("q4", "D"), ("q5", "D"),
-    ("q6", "C"), ("q7", "A"), ("q8", "C"), ("q9", "C"), ("q10", "A"),
-    ("q11", "C"), ("q12", "B"), ("q13", "A"), ("q14", "D"), ("q15", "B"),
-    ("q16", "B"), ("q17", "A"), ("q18", "A"), ("q19", "B"), ("q20", "D"),
-];
+/// How a student answered one multiple-choice question.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultipleChoiceResult {
+    /// `None` when the question was left blank.
+    pub student_answer: Option<String>,
+    pub correct_answer: String,
+    pub correct: bool,
+    pub explanation: Option<String>,
+    pub syllabus_point: Option<String>,
+}
+
+/// One grader's mark for a response graded in consensus mode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraderMark {
+    pub provider: String,
+    pub score: f64,
+}
+
+/// A teacher's correction of a question mark, kept as an audit record.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarkOverride {
+    pub question_id: String,
+    pub previous_score: f64,
+    pub previous_feedback: String,
+    /// The AI mark from before the question's first override, copied into
+    /// every later record. Missing from records made before it was kept.
+    #[serde(default)]
+    pub original_score: Option<f64>,
+    #[serde(default)]
+    pub original_feedback: Option<String>,
+    pub score: f64,
+    pub feedback: Option<String>,
+    pub reason: String,
+    pub teacher: String,
+    pub overridden_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum QuestionStatus {
+    #[default]
+    Graded,
+    Failed { error: String },
+    NeedsHuman { reason: String },
+}
+
+impl QuestionFeedback {
+    /// Feedback for a question the student left blank.
+    pub fn unanswered(question_id: &str, max_score: f64) -> Self {
+        Self {
+            question_id: question_id.to_string(),
+            max_score,
+            feedback: "No response was submitted for this question.".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Placeholder for a question no provider could grade; it scores nothing
+    /// until it is successfully re-graded.
+    pub fn failed(question_id: &str, max_score: f64, error: String) -> Self {
+        Self {
+            question_id: question_id.to_string(),
+            status: QuestionStatus::Failed { error },
+            max_score,
+            feedback: "This question could not be graded automatically and will be reviewed.".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Heading for this feedback in reports, e.g. `25a` for question `q25a`.
+    /// Falls back to the results key for aggregate entries.
+    pub fn label(&self, key: &str) -> String {
+        let id = if self.question_id.is_empty() { key } else { &self.question_id };
+        match id.strip_prefix(['q', 'Q']) {
+            Some(rest) if rest.starts_with(|c: char| c.is_ascii_digit()) => rest.to_string(),
+            _ => id.to_string(),
+        }
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(self.status, QuestionStatus::Failed { .. })
+    }
+
+    /// Forces the score into a whole number of marks between zero and the
+    /// exam-defined maximum, recording a warning for every correction.
+    pub fn enforce_mark_limits(&mut self, max_marks: f64) {
+        self.max_score = max_marks;
+
+        let awarded = self.score;
+        if !awarded.is_finite() || awarded < 0.0 {
+            self.score = 0.0;
+        } else if awarded > max_marks {
+            self.score = max_marks;
+        } else if awarded.fract() != 0.0 {
+            self.score = awarded.floor();
+        }
+
+        if self.score != awarded {
+            self.validation_warnings.push(format!(
+                "Model awarded {} out of {}; corrected to {}",
+                awarded, max_marks, self.score
+            ));
+        }
+
+        // The highest awarded criterion should agree with the score
+        let criterion_marks = self.criteria.iter().filter(|c| c.awarded).map(|c| c.marks).reduce(f64::max);
+        if let Some(marks) = criterion_marks {
+            if marks != self.score {
+                self.validation_warnings.push(format!(
+                    "Score of {} does not match the highest awarded criterion ({} marks)",
+                    self.score, marks
+                ));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CriterionResult {
+    pub description: String,
+    pub marks: f64,
+    pub awarded: bool,
+    pub comment: Option<String>,
+}
@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::Submission;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Student,
+    Teacher,
+    Admin,
+}
+
+/// A user account as stored in the `users` table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    pub user_id: String,
+    pub username: String,
+    pub display_name: String,
+    pub password_hash: String,
+    pub role: Role,
+    /// Class a student belongs to, e.g. "12CHEM1".
+    #[serde(default)]
+    pub class_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The authenticated caller, attached to each request by the auth middleware.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthUser {
+    pub user_id: String,
+    pub username: String,
+    pub display_name: String,
+    pub role: Role,
+    pub class_name: Option<String>,
+}
+
+impl From<User> for AuthUser {
+    fn from(user: User) -> Self {
+        Self {
+            user_id: user.user_id,
+            username: user.username,
+            display_name: user.display_name,
+            role: user.role,
+            class_name: user.class_name,
+        }
+    }
+}
+
+impl AuthUser {
+    /// Teachers and admins can see and moderate every submission.
+    pub fn is_staff(&self) -> bool {
+        matches!(self.role, Role::Teacher | Role::Admin)
+    }
+
+    /// Students may only see their own submissions.
+    pub fn can_view(&self, submission: &Submission) -> bool {
+        self.is_staff() || submission.student_id.as_deref() == Some(self.user_id.as_str())
+    }
+}
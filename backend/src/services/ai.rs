@@ -1,72 +1,70 @@
 use anyhow::Result;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
-use std::{collections::HashMap, sync::Arc};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 use tokio::fs;
 
-use crate::{config::Config, models::QuestionFeedback};
+use crate::{
+    chemistry::ResponseChecks,
+    config::Config,
+    models::{
+        exam::QuestionDefinition, ExamDefinition, QuestionFeedback, QuestionKind, QuestionStatus,
+    },
+    services::{
+        consensus::ConsensusSettings,
+        database::DatabaseService,
+        grading_cache::{CacheKey, GradingCache},
+        grading_schema,
+        progress::ProgressReporter,
+        prompts::PromptTemplates,
+        providers::{self, GradingProvider, GradingRequest},
+        short_answer,
+    },
+};
 
 #[derive(Clone)]
 pub struct AIService {
-    client: Client,
-    config: Arc<Config>,
+    providers: Vec<Arc<dyn GradingProvider>>,
     marking_guidelines: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct OpenAIRequest {
+    prompts: Arc<PromptTemplates>,
+    consensus: Option<ConsensusSettings>,
+    repair_attempts: usize,
+    cache: GradingCache,
+    /// The providers and consensus settings, which a cached grading must match.
     model: String,
-    messages: Vec<OpenAIMessage>,
-    max_completion_tokens: u32,
-    temperature: f32,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct OpenAIMessage {
-    role: String,
-    content: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct OpenAIResponse {
-    choices: Vec<OpenAIChoice>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct OpenAIChoice {
-    message: OpenAIMessage,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct GeminiRequest {
-    contents: Vec<GeminiContent>,
-    generation_config: GeminiGenerationConfig,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct GeminiContent {
-    parts: Vec<GeminiPart>,
-}
+impl AIService {
+    pub fn new(config: Arc<Config>, database: Arc<DatabaseService>) -> Result<Self> {
+        let providers = providers::from_config(&config)?;
+        let consensus = ConsensusSettings::from_config(&config);
+        let prompts = PromptTemplates::load(&config.prompts_path, &config.grading_prompt_version)?;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct GeminiPart {
-    text: String,
-}
+        if let Some(consensus) = &consensus {
+            if providers.len() * consensus.samples < 2 {
+                anyhow::bail!(
+                    "Consensus grading needs at least two gradings per response; \
+                     configure more GRADING_PROVIDERS or raise CONSENSUS_SAMPLES"
+                );
+            }
+        }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct GeminiGenerationConfig {
-    temperature: f32,
-    max_output_tokens: u32,
-}
+        let names: Vec<&str> = providers.iter().map(|p| p.name()).collect();
+        let model = match &consensus {
+            Some(c) => format!("{} (consensus {:?} of {} samples, spread {})", names.join(", "), c.policy, c.samples, c.max_spread),
+            None => names.join(", "),
+        };
 
-impl AIService {
-    pub fn new(config: Arc<Config>) -> Result<Self> {
-        let client = Client::new();
         Ok(Self {
-            client,
-            config,
+            providers,
             marking_guidelines: String::new(), // Will be loaded in initialize
+            prompts: Arc::new(prompts),
+            consensus,
+            repair_attempts: config.grading_repair_attempts,
+            cache: GradingCache::new(database, &config),
+            model,
         })
     }
 
@@ -75,170 +73,479 @@ impl AIService {
         Ok(())
     }
 
+    /// The exam's own marking guidelines, or the default ones.
+    fn guidelines_for<'a>(&'a self, exam: &'a ExamDefinition) -> &'a str {
+        exam.marking_guidelines.as_deref().unwrap_or(&self.marking_guidelines)
+    }
+
+    /// A service grading with scripted `MockProvider` replies keyed by question
+    /// id, without a grading cache.
+    #[cfg(test)]
+    pub fn mock(fixtures: &[(&str, &str)]) -> Self {
+        let responses = fixtures
+            .iter()
+            .map(|(question_id, reply)| (question_id.to_string(), reply.to_string()))
+            .collect();
+        let prompts = PromptTemplates::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../prompts"), "v4").unwrap();
+
+        Self {
+            providers: vec![Arc::new(providers::MockProvider::new(responses))],
+            marking_guidelines: "Mark against the criteria.".to_string(),
+            prompts: Arc::new(prompts),
+            consensus: None,
+            repair_attempts: 1,
+            cache: GradingCache::disabled(),
+            model: "Mock".to_string(),
+        }
+    }
+
+    /// Version of the prompt templates responses are graded with.
+    pub fn prompt_version(&self) -> &str {
+        self.prompts.version()
+    }
+
+    /// Grades every extended-response and short-answer question in exam order,
+    /// or only those in `only` when given. Responses the deterministic rules
+    /// and checks can mark are not sent to a model, nor are responses with a
+    /// cached grading unless `bypass_cache` is set. A question that no provider
+    /// can grade is recorded as failed rather than aborting the rest of the
+    /// submission. Each finished question is reported to `progress`.
     pub async fn grade_extended_responses(
         &self,
+        exam: &ExamDefinition,
         responses: &HashMap<String, Value>,
-    ) -> Result<Vec<QuestionFeedback>> {
+        only: Option<&HashSet<String>>,
+        bypass_cache: bool,
+        progress: &ProgressReporter,
+    ) -> Vec<QuestionFeedback> {
         let mut results = Vec::new();
 
-        for (question_id, response) in responses {
-            let feedback = self.grade_single_response(question_id, response).await?;
+        for question_id in responses.keys() {
+            let known = exam.questions().any(|q| &q.id == question_id && q.kind != QuestionKind::MultipleChoice)
+                || exam.sections.iter().flat_map(|s| &s.questions).any(|q| &q.id == question_id && !q.parts.is_empty());
+            if !known {
+                tracing::warn!("Ignoring response to unknown question {} for exam {}", question_id, exam.id);
+            }
+        }
+
+        let questions: Vec<_> = exam
+            .questions()
+            .filter(|q| q.kind != QuestionKind::MultipleChoice)
+            .filter(|q| match only {
+                Some(ids) => ids.contains(&q.id),
+                None => true,
+            })
+            .collect();
+        progress.started(questions.len());
+
+        for question in questions {
+            let parent = exam.parent_of(&question.id);
+            let mut feedback = match response_for(responses, question, parent) {
+                Some(response) if !is_blank_response(response) => {
+                    let checks = ResponseChecks::run(question, &response_text(response));
+                    let marked = short_answer::mark(question, response).or_else(|| checks.conclusive_feedback(question));
+                    let graded = match marked {
+                        Some(feedback) => Ok(feedback),
+                        None => {
+                            let key = self.cache_key(exam, question, parent, response);
+                            let cached = if bypass_cache { None } else { self.cache.get(&key).await };
+                            match cached {
+                                Some(feedback) => Ok(feedback),
+                                None => {
+                                    let graded = self.grade_single_response(exam, question, parent, response, &checks, progress).await;
+                                    if let Ok(feedback) = &graded {
+                                        self.cache.store(&key, feedback).await;
+                                    }
+                                    graded
+                                }
+                            }
+                        }
+                    };
+                    match graded {
+                        Ok(feedback) => checks.record(feedback),
+                        Err(e) => {
+                            tracing::error!("Could not grade {}: {}", question.id, e);
+                            let failed = checks.record(QuestionFeedback::failed(&question.id, question.marks, e.to_string()));
+                            progress.question_done(&failed);
+                            results.push(failed);
+                            continue;
+                        }
+                    }
+                }
+                _ => QuestionFeedback::unanswered(&question.id, question.marks),
+            };
+
+            // Never trust the model's arithmetic over the exam definition
+            feedback.enforce_mark_limits(question.marks);
+            if !feedback.validation_warnings.is_empty() {
+                tracing::warn!(
+                    "Corrected AI marks for {}: {}",
+                    question.id,
+                    feedback.validation_warnings.join("; ")
+                );
+            }
+            progress.question_done(&feedback);
             results.push(feedback);
         }
 
-        Ok(results)
+        results
+    }
+
+    fn cache_key(
+        &self,
+        exam: &ExamDefinition,
+        question: &QuestionDefinition,
+        parent: Option<&QuestionDefinition>,
+        response: &Value,
+    ) -> String {
+        CacheKey {
+            subject: &exam.subject,
+            guidelines: self.guidelines_for(exam),
+            question,
+            parent,
+            response,
+        }
+        .digest(self.prompts.version(), &self.model)
     }
 
     async fn grade_single_response(
         &self,
-        question_id: &str,
+        exam: &ExamDefinition,
+        question: &QuestionDefinition,
+        parent: Option<&QuestionDefinition>,
         response: &Value,
+        checks: &ResponseChecks,
+        progress: &ProgressReporter,
     ) -> Result<QuestionFeedback> {
-        // Try OpenAI first, fallback to Gemini if it fails
-        match self.grade_with_openai(question_id, response).await {
-            Ok(feedback) => Ok(feedback),
-            Err(e) => {
-                tracing::warn!("OpenAI grading failed for {}: {}. Trying Gemini.", question_id, e);
-                self.grade_with_gemini(question_id, response).await
+        let prompt = self.prompts.render(&exam.subject, self.guidelines_for(exam), question, parent, response, checks)?;
+        let schema = grading_schema::response_schema(question);
+        let request = GradingRequest {
+            question_id: &question.id,
+            system_prompt: &prompt.system,
+            user_prompt: &prompt.user,
+            response_schema: Some(&schema),
+        };
+
+        match &self.consensus {
+            Some(consensus) => self.grade_with_consensus(consensus, &request, question).await,
+            None => self.grade_with_fallback(&request, question, progress).await,
+        }
+    }
+
+    /// Tries each provider in order, falling back to the next one on error.
+    async fn grade_with_fallback(
+        &self,
+        request: &GradingRequest<'_>,
+        question: &QuestionDefinition,
+        progress: &ProgressReporter,
+    ) -> Result<QuestionFeedback> {
+        let mut last_error = None;
+        for (i, provider) in self.providers.iter().enumerate() {
+            match self.complete_validated(provider.as_ref(), request, question).await {
+                Ok(feedback) => return Ok(feedback),
+                Err(e) => {
+                    tracing::warn!("{} grading failed for {}: {}", provider.name(), question.id, e);
+                    if let Some(next) = self.providers.get(i + 1) {
+                        progress.retrying(&question.id, provider.name(), next.name());
+                    }
+                    last_error = Some(e);
+                }
             }
         }
+
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No grading providers configured")))
     }
 
-    async fn grade_with_openai(
+    /// Grades with every provider, `samples` times each, and combines the marks.
+    /// Fails only if no grading succeeds.
+    async fn grade_with_consensus(
         &self,
-        question_id: &str,
-        response: &Value,
+        consensus: &ConsensusSettings,
+        request: &GradingRequest<'_>,
+        question: &QuestionDefinition,
     ) -> Result<QuestionFeedback> {
-        let prompt = self.create_grading_prompt(question_id, response);
-
-        let request = OpenAIRequest {
-            model: "o1-mini".to_string(),
-            messages: vec![
-                OpenAIMessage {
-                    role: "system".to_string(),
-                    content: self.marking_guidelines.clone(),
-                },
-                OpenAIMessage {
-                    role: "user".to_string(),
-                    content: prompt,
-                },
-            ],
-            max_completion_tokens: 2000,
-            temperature: 0.1,
-        };
+        let expected = self.providers.len() * consensus.samples;
+        let mut gradings = Vec::with_capacity(expected);
+        let mut last_error = None;
 
-        let response = self
-            .client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.config.openai_api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await?;
+        for provider in &self.providers {
+            for _ in 0..consensus.samples {
+                match self.complete_validated(provider.as_ref(), request, question).await {
+                    Ok(mut feedback) => {
+                        feedback.enforce_mark_limits(question.marks);
+                        gradings.push((provider.name().to_string(), feedback));
+                    }
+                    Err(e) => {
+                        tracing::warn!("{} grading failed for {}: {}", provider.name(), question.id, e);
+                        last_error = Some(e);
+                    }
+                }
+            }
+        }
 
-        let openai_response: OpenAIResponse = response.json().await?;
-        let content = &openai_response.choices[0].message.content;
+        let feedback = consensus
+            .combine(gradings, expected)
+            .ok_or_else(|| last_error.unwrap_or_else(|| anyhow::anyhow!("No grading providers configured")))?;
 
-        self.parse_ai_feedback(content, "OpenAI o1-mini")
+        if let QuestionStatus::NeedsHuman { reason } = &feedback.status {
+            tracing::warn!("Flagged {} for human review: {}", question.id, reason);
+        }
+        Ok(feedback)
     }
 
-    async fn grade_with_gemini(
+    /// Asks one provider for a grading, sending invalid output back with the
+    /// validation problems until it conforms or the repair attempts run out.
+    async fn complete_validated(
         &self,
-        question_id: &str,
-        response: &Value,
+        provider: &dyn GradingProvider,
+        request: &GradingRequest<'_>,
+        question: &QuestionDefinition,
     ) -> Result<QuestionFeedback> {
-        let prompt = format!(
-            "{}\n\nQuestion ID: {}\nStudent Response: {}\n\nPlease provide detailed feedback.",
-            self.marking_guidelines,
-            question_id,
-            serde_json::to_string_pretty(response)?
-        );
-
-        let request = GeminiRequest {
-            contents: vec![GeminiContent {
-                parts: vec![GeminiPart { text: prompt }],
-            }],
-            generation_config: GeminiGenerationConfig {
-                temperature: 0.1,
-                max_output_tokens: 2000,
-            },
-        };
+        let mut content = provider.complete(request).await?;
+
+        for attempt in 0..=self.repair_attempts {
+            let problems = match grading_schema::parse_response(&content, question) {
+                Ok(feedback) => return Ok(feedback),
+                Err(problems) => problems,
+            };
+            tracing::warn!(
+                "{} returned invalid grading output for {}: {}",
+                provider.name(),
+                question.id,
+                problems.join("; ")
+            );
 
-        let response = self
-            .client
-            .post(&format!(
-                "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.0-flash-exp:generateContent?key={}",
-                self.config.gemini_api_key
-            ))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await?;
-
-        let gemini_response: Value = response.json().await?;
-        let content = gemini_response["candidates"][0]["content"]["parts"][0]["text"]
-            .as_str()
-            .unwrap_or("");
-
-        self.parse_ai_feedback(content, "Gemini 2.5 Pro")
-    }
-
-    fn create_grading_prompt(&self, question_id: &str, response: &Value) -> String {
-        format!(
-            "Grade the following HSC Chemistry response:\n\n\
-            Question ID: {}\n\
-            Student Response: {}\n\n\
-            Please provide:\n\
-            1. A score out of the maximum marks for this question\n\
-            2. Specific feedback on strengths and areas for improvement\n\
-            3. Band estimate if applicable\n\
-            4. Suggestions for improvement\n\n\
-            Format your response as JSON with the following structure:\n\
-            {{\n\
-              \"score\": <number>,\n\
-              \"max_score\": <number>,\n\
-              \"feedback\": \"<detailed feedback>\",\n\
-              \"strengths\": [\"<strength1>\", \"<strength2>\"],\n\
-              \"improvements\": [\"<improvement1>\", \"<improvement2>\"],\n\
-              \"band_estimate\": \"<band>\"\n\
-            }}",
-            question_id,
-            serde_json::to_string_pretty(response).unwrap_or_default()
+            if attempt < self.repair_attempts {
+                let repair = grading_schema::repair_prompt(request.user_prompt, &content, &problems);
+                content = provider
+                    .complete(&GradingRequest {
+                        user_prompt: &repair,
+                        ..request.clone()
+                    })
+                    .await?;
+            }
+        }
+
+        anyhow::bail!(
+            "{} returned invalid grading output after {} attempts",
+            provider.name(),
+            self.repair_attempts + 1
         )
     }
+}
 
-    fn parse_ai_feedback(&self, content: &str, provider: &str) -> Result<QuestionFeedback> {
-        // Extract JSON from AI response
-        let json_start = content.find('{').unwrap_or(0);
-        let json_end = content.rfind('}').map(|i| i + 1).unwrap_or(content.len());
-        let json_str = &content[json_start..json_end];
-
-        let feedback_data: Value = serde_json::from_str(json_str)
-            .or_else(|_| {
-                // Fallback parsing if JSON is malformed
-                Ok(json!({
-                    "score": 0.0,
-                    "max_score": 1.0,
-                    "feedback": content,
-                    "strengths": [],
-                    "improvements": ["Response could not be parsed properly"],
-                    "band_estimate": "Unable to determine"
-                }))
-            })?;
-
-        Ok(QuestionFeedback {
-            score: feedback_data["score"].as_f64().unwrap_or(0.0),
-            max_score: feedback_data["max_score"].as_f64().unwrap_or(1.0),
-            feedback: feedback_data["feedback"].as_str().unwrap_or(content).to_string(),
-            strengths: feedback_data["strengths"]
-                .as_array()
-                .map(|arr| arr.iter().map(|v| v.as_str().unwrap_or("").to_string()).collect())
-                .unwrap_or_default(),
-            improvements: feedback_data["improvements"]
-                .as_array()
-                .map(|arr| arr.iter().map(|v| v.as_str().unwrap_or("").to_string()).collect())
-                .unwrap_or_default(),
-            band_estimate: feedback_data["band_estimate"].as_str().map(|s| s.to_string()),
-        })
+/// A part's response is sent either under its own id (`q25a`) or nested under
+/// its question by suffix (`{"q25": {"a": ...}}`).
+fn response_for<'a>(
+    responses: &'a HashMap<String, Value>,
+    question: &QuestionDefinition,
+    parent: Option<&QuestionDefinition>,
+) -> Option<&'a Value> {
+    responses.get(&question.id).or_else(|| {
+        let parent = parent?;
+        let suffix = question.id.strip_prefix(&parent.id)?;
+        responses.get(&parent.id)?.get(suffix)
+    })
+}
+
+/// Every piece of text in a response, one per line, for the deterministic
+/// checks. Fields are labelled with their names, e.g. `chain_1: butanal`.
+fn response_text(response: &Value) -> String {
+    match response {
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(response_text).collect::<Vec<_>>().join("\n"),
+        Value::Object(fields) => fields
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, response_text(value)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+fn is_blank_response(response: &Value) -> bool {
+    match response {
+        Value::Null => true,
+        Value::String(text) => text.trim().is_empty(),
+        Value::Array(items) => items.iter().all(is_blank_response),
+        Value::Object(fields) => fields.values().all(is_blank_response),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::progress::ProgressHub;
+    use serde_json::json;
+
+    const EXAM: &str = r#"
+id = "mock-exam"
+title = "Mock Exam"
+subject = "Chemistry"
+
+[[sections]]
+name = "Section I"
+
+[[sections.questions]]
+id = "q1"
+kind = "multiple_choice"
+marks = 1
+answer = "B"
+
+[[sections]]
+name = "Section II"
+
+[[sections.questions]]
+id = "q2"
+text = "Explain why ethanol is soluble in water."
+marks = 3
+
+[[sections.questions.criteria]]
+marks = 3
+description = "Explains hydrogen bonding between ethanol and water"
+
+[[sections.questions.criteria]]
+marks = 1
+description = "Mentions polarity"
+
+[[sections.questions]]
+id = "q3"
+text = "Describe a test for an alkene."
+marks = 2
+
+[[sections.questions]]
+id = "q4"
+text = "Outline one use of esters."
+marks = 2
+
+[[sections.questions]]
+id = "q5"
+text = "Assess the impact of fuels."
+marks = 3
+"#;
+
+    #[tokio::test]
+    async fn grades_extended_responses_with_the_mock_provider() {
+        let exam: ExamDefinition = toml::from_str(EXAM).unwrap();
+        exam.validate().unwrap();
+
+        let service = AIService::mock(&[
+            (
+                "q2",
+                r#"{"score": 3, "criteria": [{"id": "C1", "awarded": true, "comment": "Hydrogen bonding explained"}],
+                    "feedback": "Clear explanation.", "strengths": [], "improvements": [], "band_estimate": "Band 6"}"#,
+            ),
+            ("q3", "I think this deserves 2 marks"),
+            (
+                "q4",
+                r#"{"score": 1.5, "criteria": [], "feedback": "Partly correct.", "strengths": [],
+                    "improvements": ["Name a specific ester"], "band_estimate": null}"#,
+            ),
+        ]);
+        let responses = HashMap::from([
+            ("q2".to_string(), json!("Ethanol forms hydrogen bonds with water.")),
+            ("q3".to_string(), json!("Add bromine water and it decolourises.")),
+            ("q4".to_string(), json!("Esters are used as flavourings.")),
+            ("q5".to_string(), json!("   ")),
+        ]);
+        let progress = Arc::new(ProgressHub::new()).reporter("MOCK-1");
+
+        let results = service.grade_extended_responses(&exam, &responses, None, false, &progress).await;
+        let ids: Vec<&str> = results.iter().map(|f| f.question_id.as_str()).collect();
+        assert_eq!(ids, ["q2", "q3", "q4", "q5"]);
+
+        let graded = &results[0];
+        assert_eq!(graded.status, QuestionStatus::Graded);
+        assert_eq!((graded.score, graded.max_score), (3.0, 3.0));
+        assert!(graded.criteria[0].awarded);
+        assert!(!graded.criteria[1].awarded);
+
+        // Output that never matches the schema fails the question, not the submission
+        let invalid = &results[1];
+        assert!(invalid.is_failed());
+        assert_eq!(invalid.score, 0.0);
+
+        // A fractional mark is accepted by the schema but rounded down
+        let clamped = &results[2];
+        assert_eq!(clamped.status, QuestionStatus::Graded);
+        assert_eq!(clamped.score, 1.0);
+        assert_eq!(clamped.validation_warnings, ["Model awarded 1.5 out of 2; corrected to 1"]);
+
+        let unanswered = &results[3];
+        assert_eq!((unanswered.score, unanswered.max_score), (0.0, 3.0));
+        assert_eq!(unanswered.feedback, "No response was submitted for this question.");
+    }
+
+    #[tokio::test]
+    async fn grades_only_the_requested_questions() {
+        let exam: ExamDefinition = toml::from_str(EXAM).unwrap();
+        let service = AIService::mock(&[(
+            "*",
+            r#"{"score": 1, "criteria": [], "feedback": "Scripted.", "strengths": [], "improvements": [], "band_estimate": null}"#,
+        )]);
+        let responses = HashMap::from([
+            ("q3".to_string(), json!("Bromine water")),
+            ("q4".to_string(), json!("Perfumes")),
+        ]);
+        let only = HashSet::from(["q4".to_string()]);
+        let progress = Arc::new(ProgressHub::new()).reporter("MOCK-2");
+
+        let results = service.grade_extended_responses(&exam, &responses, Some(&only), false, &progress).await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].question_id, "q4");
+        assert_eq!(results[0].score, 1.0);
+    }
+
+    /// Replies with each scripted reply in turn, recording the prompts it was sent.
+    struct SequenceProvider {
+        replies: std::sync::Mutex<Vec<&'static str>>,
+        prompts: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl GradingProvider for SequenceProvider {
+        fn name(&self) -> &str {
+            "Sequence"
+        }
+
+        async fn complete(&self, request: &GradingRequest<'_>) -> Result<String> {
+            self.prompts.lock().unwrap().push(request.user_prompt.to_string());
+            Ok(self.replies.lock().unwrap().remove(0).to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn accepts_a_repaired_reply_on_the_second_attempt() {
+        let exam: ExamDefinition = toml::from_str(EXAM).unwrap();
+        let question = exam.questions().find(|q| q.id == "q3").unwrap();
+        let provider = SequenceProvider {
+            replies: std::sync::Mutex::new(vec![
+                r#"{"score": 3, "criteria": [], "feedback": "Too generous.", "strengths": [], "improvements": [], "band_estimate": null}"#,
+                r#"{"score": 2, "criteria": [], "feedback": "Correct test.", "strengths": [], "improvements": [], "band_estimate": null}"#,
+            ]),
+            prompts: std::sync::Mutex::new(Vec::new()),
+        };
+        let request = GradingRequest {
+            question_id: "q3",
+            system_prompt: "Grade.",
+            user_prompt: "Grade q3.",
+            response_schema: None,
+        };
+
+        let feedback = AIService::mock(&[]).complete_validated(&provider, &request, question).await.unwrap();
+        assert_eq!((feedback.score, feedback.feedback.as_str()), (2.0, "Correct test."));
+
+        let prompts = provider.prompts.lock().unwrap().clone();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].contains("- score 3 must be between 0 and 2"), "{}", prompts[1]);
+
+        // With no attempts left the question fails
+        let provider = SequenceProvider {
+            replies: std::sync::Mutex::new(vec!["not json", "still not json"]),
+            prompts: std::sync::Mutex::new(Vec::new()),
+        };
+        let error = AIService::mock(&[]).complete_validated(&provider, &request, question).await.unwrap_err();
+        assert_eq!(error.to_string(), "Sequence returned invalid grading output after 2 attempts");
     }
 }
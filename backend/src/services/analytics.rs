@@ -0,0 +1,291 @@
+use serde::Serialize;
+
+use crate::models::{ExamDefinition, QuestionKind, Submission};
+
+const OPTIONS: &[&str] = &["A", "B", "C", "D"];
+/// Share of the cohort in each of the upper and lower groups (Kelley's 27%).
+const GROUP_FRACTION: f64 = 0.27;
+
+/// Cohort statistics for every multiple-choice item in an exam.
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemAnalysis {
+    pub exam_id: String,
+    /// Graded submissions included in the analysis.
+    pub cohort_size: usize,
+    /// Students in each of the upper and lower groups used for discrimination.
+    pub group_size: usize,
+    pub items: Vec<ItemStatistics>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemStatistics {
+    pub question_id: String,
+    pub correct_answer: String,
+    /// Percentage of the cohort answering correctly.
+    pub facility: f64,
+    /// Proportion correct in the upper group minus the lower group, from -1 to 1.
+    pub discrimination: Option<f64>,
+    /// Correlation between answering correctly and total score.
+    pub point_biserial: Option<f64>,
+    pub options: Vec<OptionFrequency>,
+    /// Students who left the item blank or gave an answer other than A-D.
+    pub omitted: usize,
+}
+
+/// How often one option was chosen.
+#[derive(Debug, Clone, Serialize)]
+pub struct OptionFrequency {
+    pub option: String,
+    pub count: usize,
+    pub percentage: f64,
+    pub correct: bool,
+}
+
+/// Analyses multiple-choice items across graded submissions. Students are
+/// ranked by total score, so ungraded submissions are left out.
+pub fn analyse_multiple_choice(exam: &ExamDefinition, submissions: &[Submission]) -> ItemAnalysis {
+    let mut cohort: Vec<(&Submission, f64)> = submissions
+        .iter()
+        .filter_map(|s| s.results.as_ref().map(|r| (s, r.total_score)))
+        .collect();
+    cohort.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    let cohort_size = cohort.len();
+    let group_size = ((cohort_size as f64 * GROUP_FRACTION).round() as usize).min(cohort_size / 2);
+    let totals: Vec<f64> = cohort.iter().map(|(_, total)| *total).collect();
+
+    let items = exam
+        .questions()
+        .filter(|q| q.kind == QuestionKind::MultipleChoice)
+        .map(|question| {
+            let correct_answer = question.answer.clone().unwrap_or_default();
+            let answers: Vec<Option<String>> = cohort
+                .iter()
+                .map(|(s, _)| {
+                    s.responses
+                        .multiple_choice
+                        .get(&question.id)
+                        .map(|a| a.trim().to_uppercase())
+                        .filter(|a| OPTIONS.contains(&a.as_str()))
+                })
+                .collect();
+            let correct: Vec<bool> = answers
+                .iter()
+                .map(|a| a.as_deref() == Some(correct_answer.as_str()))
+                .collect();
+
+            let options = OPTIONS
+                .iter()
+                .map(|option| {
+                    let count = answers.iter().filter(|a| a.as_deref() == Some(*option)).count();
+                    OptionFrequency {
+                        option: option.to_string(),
+                        count,
+                        percentage: percentage(count, cohort_size),
+                        correct: *option == correct_answer,
+                    }
+                })
+                .collect();
+
+            ItemStatistics {
+                question_id: question.id.clone(),
+                facility: percentage(correct.iter().filter(|c| **c).count(), cohort_size),
+                discrimination: discrimination(&correct, group_size),
+                point_biserial: point_biserial(&correct, &totals),
+                options,
+                omitted: answers.iter().filter(|a| a.is_none()).count(),
+                correct_answer,
+            }
+        })
+        .collect();
+
+    ItemAnalysis {
+        exam_id: exam.id.clone(),
+        cohort_size,
+        group_size,
+        items,
+    }
+}
+
+fn percentage(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64 * 100.0
+    }
+}
+
+/// `correct` must be ordered from the highest total score to the lowest.
+fn discrimination(correct: &[bool], group_size: usize) -> Option<f64> {
+    if group_size == 0 {
+        return None;
+    }
+    let proportion = |group: &[bool]| group.iter().filter(|c| **c).count() as f64 / group_size as f64;
+    Some(proportion(&correct[..group_size]) - proportion(&correct[correct.len() - group_size..]))
+}
+
+/// Undefined when everyone or no one answered correctly, or all totals are equal.
+fn point_biserial(correct: &[bool], totals: &[f64]) -> Option<f64> {
+    let n = totals.len() as f64;
+    let right: Vec<f64> = totals.iter().zip(correct).filter(|(_, c)| **c).map(|(t, _)| *t).collect();
+    let wrong: Vec<f64> = totals.iter().zip(correct).filter(|(_, c)| !**c).map(|(t, _)| *t).collect();
+    if right.is_empty() || wrong.is_empty() {
+        return None;
+    }
+
+    let mean = totals.iter().sum::<f64>() / n;
+    let std_dev = (totals.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / n).sqrt();
+    if std_dev == 0.0 {
+        return None;
+    }
+
+    let p = right.len() as f64 / n;
+    let mean_right = right.iter().sum::<f64>() / right.len() as f64;
+    let mean_wrong = wrong.iter().sum::<f64>() / wrong.len() as f64;
+    Some((mean_right - mean_wrong) / std_dev * (p * (1.0 - p)).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ExamResponses, GradingResults, GradingStatus};
+    use chrono::Utc;
+    use std::collections::HashMap;
+
+    const EXAM: &str = r#"
+id = "items"
+title = "Items"
+
+[[sections]]
+name = "Section I"
+
+[[sections.questions]]
+id = "q1"
+kind = "multiple_choice"
+marks = 1
+answer = "A"
+
+[[sections.questions]]
+id = "q2"
+kind = "multiple_choice"
+marks = 1
+answer = "B"
+"#;
+
+    /// A submission answering q1 and q2, graded with `total` unless it is `None`.
+    fn submission(q1: &str, q2: &str, total: Option<f64>) -> Submission {
+        Submission {
+            id: uuid::Uuid::new_v4(),
+            submission_code: format!("S-{}-{}-{:?}", q1, q2, total),
+            exam_id: "items".to_string(),
+            student_id: None,
+            responses: ExamResponses {
+                multiple_choice: HashMap::from([
+                    ("q1".to_string(), q1.to_string()),
+                    ("q2".to_string(), q2.to_string()),
+                ]),
+                extended_response: HashMap::new(),
+                time_taken_minutes: 60.0,
+            },
+            submitted_at: Utc::now(),
+            grading_status: GradingStatus::Completed,
+            results: total.map(|total_score| GradingResults {
+                total_score,
+                max_score: 10.0,
+                section_scores: HashMap::new(),
+                question_feedback: HashMap::new(),
+                overall_feedback: String::new(),
+                ai_provider_used: String::new(),
+                graded_at: Utc::now(),
+                band: None,
+                prompt_version: None,
+                moderation_history: Vec::new(),
+            }),
+        }
+    }
+
+    fn analyse(submissions: &[Submission]) -> ItemAnalysis {
+        analyse_multiple_choice(&toml::from_str(EXAM).unwrap(), submissions)
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.unwrap();
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn analyses_a_small_cohort() {
+        // Out of rank order, with an ungraded submission that is left out
+        let analysis = analyse(&[
+            submission("B", "B", Some(6.0)),
+            submission("A", "B", Some(10.0)),
+            submission("", "B", Some(2.0)),
+            submission("A", "B", None),
+            submission(" a", "B", Some(8.0)),
+            submission("A", "B", Some(4.0)),
+        ]);
+        assert_eq!((analysis.cohort_size, analysis.group_size), (5, 1));
+
+        let q1 = &analysis.items[0];
+        assert_eq!(q1.facility, 60.0);
+        // Top student (10) right, bottom student (2) blank
+        assert_eq!(q1.discrimination, Some(1.0));
+        // Right: 10, 8, 4 (mean 22/3); wrong: 6, 2 (mean 4); σ = √8; p = 0.6
+        assert_close(q1.point_biserial, (22.0 / 3.0 - 4.0) / 8f64.sqrt() * 0.24f64.sqrt());
+        assert_eq!(q1.omitted, 1);
+
+        let counts: Vec<(&str, usize, f64, bool)> = q1
+            .options
+            .iter()
+            .map(|o| (o.option.as_str(), o.count, o.percentage, o.correct))
+            .collect();
+        assert_eq!(
+            counts,
+            [("A", 3, 60.0, true), ("B", 1, 20.0, false), ("C", 0, 0.0, false), ("D", 0, 0.0, false)]
+        );
+    }
+
+    #[test]
+    fn leaves_statistics_undefined_when_everyone_is_correct() {
+        let analysis = analyse(&[
+            submission("A", "B", Some(9.0)),
+            submission("C", "B", Some(5.0)),
+            submission("D", "B", Some(1.0)),
+            submission("A", "B", Some(7.0)),
+        ]);
+
+        let q2 = &analysis.items[1];
+        assert_eq!(q2.facility, 100.0);
+        assert_eq!(q2.discrimination, Some(0.0));
+        assert_eq!(q2.point_biserial, None);
+    }
+
+    #[test]
+    fn leaves_point_biserial_undefined_when_all_totals_are_equal() {
+        let analysis = analyse(&[submission("A", "B", Some(5.0)), submission("C", "B", Some(5.0))]);
+        assert_eq!(analysis.items[0].facility, 50.0);
+        assert_eq!(analysis.items[0].point_biserial, None);
+    }
+
+    #[test]
+    fn handles_cohorts_smaller_than_four() {
+        let three = analyse(&[
+            submission("A", "B", Some(9.0)),
+            submission("A", "C", Some(5.0)),
+            submission("B", "X", Some(1.0)),
+        ]);
+        assert_eq!(three.group_size, 1);
+        assert_eq!(three.items[0].discrimination, Some(1.0));
+        // An answer other than A-D counts as omitted
+        assert_eq!(three.items[1].omitted, 1);
+
+        let one = analyse(&[submission("A", "B", Some(9.0))]);
+        assert_eq!(one.group_size, 0);
+        assert_eq!(one.items[0].discrimination, None);
+        assert_eq!(one.items[0].point_biserial, None);
+
+        let none = analyse(&[]);
+        assert_eq!(none.cohort_size, 0);
+        assert_eq!(none.items[0].facility, 0.0);
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::Result;
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use chrono::{DateTime, Duration, Utc};
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    models::{AuthUser, Role, User},
+    services::database::DatabaseService,
+};
+
+/// Password hashing and session management.
+///
+/// Session tokens are random 256-bit values handed to the client once; only
+/// their SHA-256 hash is stored, so a database leak does not expose live sessions.
+pub struct AuthService {
+    database: Arc<DatabaseService>,
+    session_ttl: Duration,
+}
+
+/// A freshly issued session token.
+pub struct Session {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub user: AuthUser,
+}
+
+impl AuthService {
+    pub fn new(database: Arc<DatabaseService>, config: &Config) -> Self {
+        Self {
+            database,
+            session_ttl: Duration::hours(config.session_ttl_hours as i64),
+        }
+    }
+
+    /// Creates the configured admin account on first start.
+    pub async fn ensure_admin(&self, config: &Config) -> Result<()> {
+        let (Some(username), Some(password)) = (&config.admin_username, &config.admin_password) else {
+            return Ok(());
+        };
+
+        if self.database.get_user_by_username(username).await?.is_none() {
+            self.create_user(username, username, password, Role::Admin, None).await?;
+            tracing::info!("Created admin account '{}'", username);
+        }
+        Ok(())
+    }
+
+    pub async fn create_user(
+        &self,
+        username: &str,
+        display_name: &str,
+        password: &str,
+        role: Role,
+        class_name: Option<String>,
+    ) -> Result<User> {
+        let user = User {
+            user_id: Uuid::new_v4().to_string(),
+            username: username.to_string(),
+            display_name: display_name.to_string(),
+            password_hash: hash_password(password)?,
+            role,
+            class_name,
+            created_at: Utc::now(),
+        };
+        self.database.create_user(&user).await?;
+        Ok(user)
+    }
+
+    /// Checks a username and password, issuing a new session on success.
+    pub async fn login(&self, username: &str, password: &str) -> Result<Option<Session>> {
+        let Some(user) = self.database.get_user_by_username(username).await? else {
+            // Hash anyway so unknown usernames take as long as wrong passwords
+            let _ = hash_password(password);
+            return Ok(None);
+        };
+
+        if !verify_password(password, &user.password_hash) {
+            return Ok(None);
+        }
+
+        self.database.delete_expired_sessions().await?;
+
+        let token = generate_token();
+        let expires_at = Utc::now() + self.session_ttl;
+        self.database
+            .create_session(&hash_token(&token), &user.user_id, expires_at)
+            .await?;
+
+        Ok(Some(Session {
+            token,
+            expires_at,
+            user: user.into(),
+        }))
+    }
+
+    /// Resolves a bearer token to its user, if the session is still valid.
+    pub async fn authenticate(&self, token: &str) -> Result<Option<AuthUser>> {
+        Ok(self
+            .database
+            .get_session_user(&hash_token(token))
+            .await?
+            .map(AuthUser::from))
+    }
+
+    pub async fn logout(&self, token: &str) -> Result<()> {
+        self.database.delete_session(&hash_token(token)).await
+    }
+}
+
+fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+        .unwrap_or(false)
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
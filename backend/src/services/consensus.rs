@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::{
+    config::Config,
+    models::{GraderMark, QuestionFeedback, QuestionStatus},
+};
+
+/// How individual grader marks are combined into the awarded mark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsensusPolicy {
+    Median,
+    Mean,
+    Min,
+}
+
+impl FromStr for ConsensusPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "median" => Ok(Self::Median),
+            "mean" => Ok(Self::Mean),
+            "min" => Ok(Self::Min),
+            other => Err(format!("unknown consensus policy '{}', expected median, mean or min", other)),
+        }
+    }
+}
+
+/// Settings for grading each response with several graders.
+#[derive(Debug, Clone, Copy)]
+pub struct ConsensusSettings {
+    /// Gradings requested from each provider.
+    pub samples: usize,
+    pub policy: ConsensusPolicy,
+    /// Largest acceptable gap between the highest and lowest mark.
+    pub max_spread: f64,
+}
+
+impl ConsensusSettings {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        config.consensus_enabled.then(|| Self {
+            samples: config.consensus_samples.max(1),
+            policy: config.consensus_policy,
+            max_spread: config.consensus_max_spread,
+        })
+    }
+
+    /// Combines independent gradings of one response. Each grading must already
+    /// have its marks clamped to the question maximum.
+    ///
+    /// The awarded mark follows the policy, rounded down to a whole mark as a
+    /// single grader's fractional mark is, so 2 and 3 give 2; the feedback
+    /// comes from the grader whose mark is closest to it. The response is flagged
+    /// for human review when graders disagree by more than `max_spread` or when
+    /// fewer than two gradings succeeded.
+    // `usize::is_multiple_of` needs a newer toolchain than the Docker images build with
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn combine(&self, gradings: Vec<(String, QuestionFeedback)>, expected: usize) -> Option<QuestionFeedback> {
+        let mut scores: Vec<f64> = gradings.iter().map(|(_, g)| g.score).collect();
+        scores.sort_by(|a, b| a.total_cmp(b));
+
+        let lowest = *scores.first()?;
+        let highest = *scores.last()?;
+        let combined = match self.policy {
+            ConsensusPolicy::Min => lowest,
+            ConsensusPolicy::Mean => scores.iter().sum::<f64>() / scores.len() as f64,
+            ConsensusPolicy::Median => {
+                let middle = scores.len() / 2;
+                if scores.len() % 2 == 0 {
+                    (scores[middle - 1] + scores[middle]) / 2.0
+                } else {
+                    scores[middle]
+                }
+            }
+        }
+        .floor();
+
+        let individual_marks: Vec<GraderMark> = gradings
+            .iter()
+            .map(|(provider, g)| GraderMark {
+                provider: provider.clone(),
+                score: g.score,
+            })
+            .collect();
+
+        let validation_warnings: Vec<String> = gradings
+            .iter()
+            .flat_map(|(provider, g)| g.validation_warnings.iter().map(move |w| format!("{}: {}", provider, w)))
+            .collect();
+
+        let (_, representative) = gradings
+            .into_iter()
+            .min_by(|(_, a), (_, b)| (a.score - combined).abs().total_cmp(&(b.score - combined).abs()))?;
+
+        let status = if highest - lowest > self.max_spread {
+            QuestionStatus::NeedsHuman {
+                reason: format!("Graders disagreed: marks ranged from {} to {}", lowest, highest),
+            }
+        } else if individual_marks.len() < 2 {
+            QuestionStatus::NeedsHuman {
+                reason: format!("Only {} of {} gradings succeeded", individual_marks.len(), expected),
+            }
+        } else {
+            QuestionStatus::Graded
+        };
+
+        Some(QuestionFeedback {
+            status,
+            score: combined,
+            individual_marks,
+            validation_warnings,
+            ..representative
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(policy: ConsensusPolicy, max_spread: f64) -> ConsensusSettings {
+        ConsensusSettings {
+            samples: 1,
+            policy,
+            max_spread,
+        }
+    }
+
+    fn gradings(scores: &[f64]) -> Vec<(String, QuestionFeedback)> {
+        scores
+            .iter()
+            .enumerate()
+            .map(|(i, score)| {
+                let feedback = QuestionFeedback {
+                    question_id: "q22".to_string(),
+                    score: *score,
+                    max_score: 5.0,
+                    feedback: format!("Grader {} awarded {}", i + 1, score),
+                    ..Default::default()
+                };
+                (format!("grader-{}", i + 1), feedback)
+            })
+            .collect()
+    }
+
+    fn combine(policy: ConsensusPolicy, scores: &[f64]) -> QuestionFeedback {
+        settings(policy, 5.0).combine(gradings(scores), scores.len()).unwrap()
+    }
+
+    #[test]
+    fn takes_the_median_of_odd_and_even_grader_counts() {
+        assert_eq!(combine(ConsensusPolicy::Median, &[4.0, 1.0, 3.0]).score, 3.0);
+        // Halfway between 2 and 3 is rounded down
+        assert_eq!(combine(ConsensusPolicy::Median, &[3.0, 2.0]).score, 2.0);
+        assert_eq!(combine(ConsensusPolicy::Median, &[1.0, 4.0, 2.0, 5.0]).score, 3.0);
+    }
+
+    #[test]
+    fn takes_the_mean_or_minimum() {
+        assert_eq!(combine(ConsensusPolicy::Mean, &[2.0, 3.0, 3.0]).score, 2.0);
+        assert_eq!(combine(ConsensusPolicy::Mean, &[1.0, 3.0, 5.0]).score, 3.0);
+        assert_eq!(combine(ConsensusPolicy::Min, &[4.0, 2.0, 3.0]).score, 2.0);
+        assert_eq!("MIN".parse::<ConsensusPolicy>(), Ok(ConsensusPolicy::Min));
+        assert!("mode".parse::<ConsensusPolicy>().is_err());
+    }
+
+    #[test]
+    fn keeps_every_mark_and_the_closest_feedback() {
+        let combined = combine(ConsensusPolicy::Median, &[1.0, 4.0, 3.0]);
+        assert_eq!(combined.feedback, "Grader 3 awarded 3");
+        let marks: Vec<(&str, f64)> = combined
+            .individual_marks
+            .iter()
+            .map(|m| (m.provider.as_str(), m.score))
+            .collect();
+        assert_eq!(marks, [("grader-1", 1.0), ("grader-2", 4.0), ("grader-3", 3.0)]);
+    }
+
+    #[test]
+    fn flags_disagreement_beyond_the_spread() {
+        let within = settings(ConsensusPolicy::Median, 1.0).combine(gradings(&[2.0, 3.0, 3.0]), 3).unwrap();
+        assert_eq!(within.status, QuestionStatus::Graded);
+
+        let beyond = settings(ConsensusPolicy::Median, 1.0).combine(gradings(&[1.0, 3.0, 3.0]), 3).unwrap();
+        assert_eq!(
+            beyond.status,
+            QuestionStatus::NeedsHuman {
+                reason: "Graders disagreed: marks ranged from 1 to 3".to_string()
+            }
+        );
+        assert_eq!(beyond.score, 3.0);
+    }
+
+    #[test]
+    fn flags_too_few_gradings() {
+        let single = settings(ConsensusPolicy::Median, 1.0).combine(gradings(&[2.0]), 3).unwrap();
+        assert_eq!(
+            single.status,
+            QuestionStatus::NeedsHuman {
+                reason: "Only 1 of 3 gradings succeeded".to_string()
+            }
+        );
+        assert!(settings(ConsensusPolicy::Median, 1.0).combine(Vec::new(), 3).is_none());
+    }
+}
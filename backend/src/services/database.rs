@@ -2,7 +2,11 @@ use anyhow::Result;
 use surrealdb::{engine::local::RocksDb, Surreal};
 use uuid::Uuid;
 
-use crate::models::{Submission, GradingResults, GradingStatus};
+use chrono::{DateTime, Utc};
+
+use crate::models::{
+    ExamDefinition, GradingJob, GradingScope, Submission, GradingResults, GradingStatus, QuestionFeedback, User,
+};
 
 #[derive(Clone)]
 pub struct DatabaseService {
@@ -40,12 +44,20 @@ impl DatabaseService {
             .query("DEFINE FIELD responses ON submissions TYPE object")
             .await?;
 
+        self.db
+            .query("DEFINE FIELD exam_id ON submissions TYPE string")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD student_id ON submissions TYPE option<string>")
+            .await?;
+
         self.db
             .query("DEFINE FIELD submitted_at ON submissions TYPE datetime")
             .await?;
 
         self.db
-            .query("DEFINE FIELD grading_status ON submissions TYPE string")
+            .query("DEFINE FIELD grading_status ON submissions TYPE string | object")
             .await?;
 
         self.db
@@ -57,6 +69,173 @@ impl DatabaseService {
             .query("DEFINE INDEX submission_code_idx ON submissions FIELDS submission_code UNIQUE")
             .await?;
 
+        // Define exam table, keyed by exam id
+        self.db
+            .query("DEFINE TABLE exams SCHEMAFULL")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD exam_id ON exams TYPE string")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD definition ON exams FLEXIBLE TYPE object")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD updated_at ON exams TYPE datetime")
+            .await?;
+
+        // Define grading job queue
+        self.db
+            .query("DEFINE TABLE grading_jobs SCHEMAFULL")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD job_id ON grading_jobs TYPE string")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD submission_code ON grading_jobs TYPE string")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD status ON grading_jobs TYPE string")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD scope ON grading_jobs TYPE string")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD attempts ON grading_jobs TYPE int")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD max_attempts ON grading_jobs TYPE int")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD available_at ON grading_jobs TYPE datetime")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD lease_expires_at ON grading_jobs TYPE option<datetime>")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD last_error ON grading_jobs TYPE option<string>")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD bypass_cache ON grading_jobs TYPE bool DEFAULT false")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD created_at ON grading_jobs TYPE datetime")
+            .await?;
+
+        self.db
+            .query("DEFINE INDEX grading_jobs_status_idx ON grading_jobs FIELDS status, available_at")
+            .await?;
+
+        self.db
+            .query("DEFINE INDEX grading_jobs_submission_idx ON grading_jobs FIELDS submission_code")
+            .await?;
+
+        // One record per submission with a queued or running job, keyed by the
+        // submission code so a second job for it cannot be created
+        self.db
+            .query("DEFINE TABLE active_grading_jobs SCHEMAFULL")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD submission_code ON active_grading_jobs TYPE string")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD job_id ON active_grading_jobs TYPE string")
+            .await?;
+
+        // Define user accounts, keyed by user id
+        self.db
+            .query("DEFINE TABLE users SCHEMAFULL")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD user_id ON users TYPE string")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD username ON users TYPE string")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD display_name ON users TYPE string")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD password_hash ON users TYPE string")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD role ON users TYPE string")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD class_name ON users TYPE option<string>")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD created_at ON users TYPE datetime")
+            .await?;
+
+        self.db
+            .query("DEFINE INDEX users_username_idx ON users FIELDS username UNIQUE")
+            .await?;
+
+        // Define login sessions, keyed by a hash of the session token
+        self.db
+            .query("DEFINE TABLE sessions SCHEMAFULL")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD token_hash ON sessions TYPE string")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD user_id ON sessions TYPE string")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD expires_at ON sessions TYPE datetime")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD created_at ON sessions TYPE datetime")
+            .await?;
+
+        // Define cached model gradings, keyed by a hash of what they were graded from
+        self.db
+            .query("DEFINE TABLE grading_cache SCHEMAFULL")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD question_id ON grading_cache TYPE string")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD feedback ON grading_cache FLEXIBLE TYPE object")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD expires_at ON grading_cache TYPE datetime")
+            .await?;
+
+        self.db
+            .query("DEFINE FIELD created_at ON grading_cache TYPE datetime")
+            .await?;
+
         Ok(())
     }
 
@@ -78,6 +257,18 @@ impl DatabaseService {
         Ok(submissions.into_iter().next())
     }
 
+    /// Every submission for an exam, ordered by submission code.
+    pub async fn list_submissions_for_exam(&self, exam_id: &str) -> Result<Vec<Submission>> {
+        let mut result = self.db
+            .query("SELECT * FROM submissions WHERE exam_id = $exam_id")
+            .bind(("exam_id", exam_id))
+            .await?;
+
+        let mut submissions: Vec<Submission> = result.take(0)?;
+        submissions.sort_by(|a, b| a.submission_code.cmp(&b.submission_code));
+        Ok(submissions)
+    }
+
     pub async fn submission_exists(&self, submission_code: &str) -> Result<bool> {
         let submission = self.get_submission(submission_code).await?;
         Ok(submission.is_some())
@@ -90,7 +281,7 @@ impl DatabaseService {
     ) -> Result<()> {
         self.db
             .query("UPDATE submissions SET grading_status = $status WHERE submission_code = $code")
-            .bind(("status", serde_json::to_string(&status)?))
+            .bind(("status", status))
             .bind(("code", submission_code))
             .await?;
         Ok(())
@@ -109,6 +300,337 @@ impl DatabaseService {
         Ok(())
     }
 
+    /// Codes of submissions that have not finished grading.
+    pub async fn ungraded_submission_codes(&self) -> Result<Vec<String>> {
+        let mut result = self.db
+            .query("SELECT VALUE submission_code FROM submissions WHERE grading_status IN ['Pending', 'InProgress']")
+            .await?;
+
+        let codes: Vec<String> = result.take(0)?;
+        Ok(codes)
+    }
+
+    /// Queues a grading job unless the submission already has one queued or running.
+    /// Returns whether a new job was created.
+    pub async fn enqueue_grading_job(
+        &self,
+        submission_code: &str,
+        scope: GradingScope,
+        bypass_cache: bool,
+        max_attempts: u32,
+    ) -> Result<bool> {
+        if self.has_active_grading_job(submission_code).await? {
+            return Ok(false);
+        }
+
+        // The job and its `active_grading_jobs` record are created together, so
+        // a concurrent enqueue for the same submission fails on the record id
+        let created = self.db
+            .query(
+                "BEGIN TRANSACTION; \
+                 CREATE type::thing('active_grading_jobs', $code) SET submission_code = $code, job_id = $job_id; \
+                 CREATE type::thing('grading_jobs', $job_id) SET \
+                 job_id = $job_id, submission_code = $code, status = 'Queued', scope = $scope, \
+                 bypass_cache = $bypass_cache, attempts = 0, max_attempts = $max_attempts, \
+                 available_at = time::now(), lease_expires_at = NONE, last_error = NONE, \
+                 created_at = time::now(); \
+                 COMMIT TRANSACTION;",
+            )
+            .bind(("job_id", Uuid::new_v4().to_string()))
+            .bind(("code", submission_code))
+            .bind(("scope", scope))
+            .bind(("bypass_cache", bypass_cache))
+            .bind(("max_attempts", max_attempts))
+            .await?
+            .check();
+
+        match created {
+            Ok(_) => Ok(true),
+            Err(_) if self.has_active_grading_job(submission_code).await? => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn has_active_grading_job(&self, submission_code: &str) -> Result<bool> {
+        let mut result = self.db
+            .query(
+                "SELECT VALUE job_id FROM grading_jobs WHERE submission_code = $code AND status IN ['Queued', 'Leased']; \
+                 SELECT VALUE job_id FROM type::thing('active_grading_jobs', $code)",
+            )
+            .bind(("code", submission_code))
+            .await?;
+
+        let jobs: Vec<String> = result.take(0)?;
+        let locks: Vec<String> = result.take(1)?;
+        Ok(!jobs.is_empty() || !locks.is_empty())
+    }
+
+    /// Claims the next runnable job: a queued job whose backoff has elapsed, or a
+    /// leased job whose worker has gone away with attempts to spare. Each claim
+    /// counts as an attempt.
+    pub async fn lease_next_grading_job(&self, lease_until: DateTime<Utc>) -> Result<Option<GradingJob>> {
+        const RUNNABLE: &str = "(status = 'Queued' AND available_at <= time::now()) \
+             OR (status = 'Leased' AND lease_expires_at <= time::now() AND attempts < max_attempts)";
+
+        let mut result = self.db
+            .query(format!("SELECT * FROM grading_jobs WHERE {} ORDER BY available_at LIMIT 10", RUNNABLE))
+            .await?;
+        let candidates: Vec<GradingJob> = result.take(0)?;
+
+        for candidate in candidates {
+            // Re-check the condition in the update so two workers cannot claim the same job
+            let mut result = self.db
+                .query(format!(
+                    "UPDATE type::thing('grading_jobs', $job_id) SET status = 'Leased', attempts += 1, \
+                     lease_expires_at = <datetime> $lease_until WHERE {} RETURN AFTER",
+                    RUNNABLE
+                ))
+                .bind(("job_id", &candidate.job_id))
+                .bind(("lease_until", lease_until))
+                .await?;
+
+            let claimed: Vec<GradingJob> = result.take(0)?;
+            if let Some(job) = claimed.into_iter().next() {
+                return Ok(Some(job));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Extends the lease of a job still held by the worker that claimed it on
+    /// attempt `attempts`. Returns false if the lease has been lost.
+    pub async fn renew_grading_lease(&self, job_id: &str, attempts: u32, lease_until: DateTime<Utc>) -> Result<bool> {
+        let mut result = self.db
+            .query(
+                "UPDATE type::thing('grading_jobs', $job_id) SET lease_expires_at = <datetime> $lease_until \
+                 WHERE status = 'Leased' AND attempts = $attempts RETURN AFTER",
+            )
+            .bind(("job_id", job_id))
+            .bind(("attempts", attempts))
+            .bind(("lease_until", lease_until))
+            .await?;
+
+        let renewed: Vec<GradingJob> = result.take(0)?;
+        Ok(!renewed.is_empty())
+    }
+
+    pub async fn complete_grading_job(&self, job_id: &str) -> Result<()> {
+        self.db
+            .query(
+                "UPDATE type::thing('grading_jobs', $job_id) SET status = 'Completed', lease_expires_at = NONE; \
+                 DELETE active_grading_jobs WHERE job_id = $job_id",
+            )
+            .bind(("job_id", job_id))
+            .await?;
+        Ok(())
+    }
+
+    /// Returns a job to the queue, to be retried no earlier than `available_at`.
+    pub async fn retry_grading_job(&self, job_id: &str, error: &str, available_at: DateTime<Utc>) -> Result<()> {
+        self.db
+            .query(
+                "UPDATE type::thing('grading_jobs', $job_id) SET status = 'Queued', \
+                 available_at = <datetime> $available_at, lease_expires_at = NONE, last_error = $error",
+            )
+            .bind(("job_id", job_id))
+            .bind(("available_at", available_at))
+            .bind(("error", error))
+            .await?;
+        Ok(())
+    }
+
+    /// Fails leased jobs whose worker has gone away on their last attempt, such
+    /// as a job that crashes the process every time, and returns them.
+    pub async fn fail_abandoned_grading_jobs(&self, error: &str) -> Result<Vec<GradingJob>> {
+        let mut result = self.db
+            .query(
+                "UPDATE grading_jobs SET status = 'Failed', lease_expires_at = NONE, last_error = $error \
+                 WHERE status = 'Leased' AND lease_expires_at <= time::now() AND attempts >= max_attempts \
+                 RETURN AFTER",
+            )
+            .bind(("error", error))
+            .await?;
+
+        let jobs: Vec<GradingJob> = result.take(0)?;
+        let job_ids: Vec<&str> = jobs.iter().map(|job| job.job_id.as_str()).collect();
+        self.db
+            .query("DELETE active_grading_jobs WHERE job_id INSIDE $job_ids")
+            .bind(("job_ids", job_ids))
+            .await?;
+        Ok(jobs)
+    }
+
+    pub async fn fail_grading_job(&self, job_id: &str, error: &str) -> Result<()> {
+        self.db
+            .query(
+                "UPDATE type::thing('grading_jobs', $job_id) SET status = 'Failed', lease_expires_at = NONE, \
+                 last_error = $error; \
+                 DELETE active_grading_jobs WHERE job_id = $job_id",
+            )
+            .bind(("job_id", job_id))
+            .bind(("error", error))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn store_exam(&self, exam: &ExamDefinition) -> Result<()> {
+        self.db
+            .query("UPDATE type::thing('exams', $id) CONTENT { exam_id: $id, definition: $definition, updated_at: time::now() }")
+            .bind(("id", &exam.id))
+            .bind(("definition", exam))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_exam(&self, exam_id: &str) -> Result<Option<ExamDefinition>> {
+        let mut result = self.db
+            .query("SELECT VALUE definition FROM exams WHERE exam_id = $id")
+            .bind(("id", exam_id))
+            .await?;
+
+        let exams: Vec<ExamDefinition> = result.take(0)?;
+        Ok(exams.into_iter().next())
+    }
+
+    pub async fn list_exams(&self) -> Result<Vec<ExamDefinition>> {
+        let mut result = self.db
+            .query("SELECT VALUE definition FROM exams")
+            .await?;
+
+        let mut exams: Vec<ExamDefinition> = result.take(0)?;
+        exams.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(exams)
+    }
+
+    /// Stores a new account. Fails if the username is already taken.
+    pub async fn create_user(&self, user: &User) -> Result<()> {
+        self.db
+            .query(
+                "CREATE type::thing('users', $user_id) SET \
+                 user_id = $user_id, username = $username, display_name = $display_name, \
+                 password_hash = $password_hash, role = $role, class_name = $class_name, \
+                 created_at = time::now()",
+            )
+            .bind(("user_id", &user.user_id))
+            .bind(("username", &user.username))
+            .bind(("display_name", &user.display_name))
+            .bind(("password_hash", &user.password_hash))
+            .bind(("role", user.role))
+            .bind(("class_name", &user.class_name))
+            .await?
+            .check()?;
+        Ok(())
+    }
+
+    pub async fn get_user_by_username(&self, username: &str) -> Result<Option<User>> {
+        let mut result = self.db
+            .query("SELECT * FROM users WHERE username = $username")
+            .bind(("username", username))
+            .await?;
+
+        let users: Vec<User> = result.take(0)?;
+        Ok(users.into_iter().next())
+    }
+
+    pub async fn list_users(&self) -> Result<Vec<User>> {
+        let mut result = self.db
+            .query("SELECT * FROM users")
+            .await?;
+
+        let mut users: Vec<User> = result.take(0)?;
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        Ok(users)
+    }
+
+    pub async fn create_session(&self, token_hash: &str, user_id: &str, expires_at: DateTime<Utc>) -> Result<()> {
+        self.db
+            .query(
+                "CREATE type::thing('sessions', $token_hash) SET token_hash = $token_hash, \
+                 user_id = $user_id, expires_at = <datetime> $expires_at, created_at = time::now()",
+            )
+            .bind(("token_hash", token_hash))
+            .bind(("user_id", user_id))
+            .bind(("expires_at", expires_at))
+            .await?
+            .check()?;
+        Ok(())
+    }
+
+    /// The user owning an unexpired session.
+    pub async fn get_session_user(&self, token_hash: &str) -> Result<Option<User>> {
+        let mut result = self.db
+            .query("SELECT VALUE user_id FROM sessions WHERE token_hash = $token_hash AND expires_at > time::now()")
+            .bind(("token_hash", token_hash))
+            .await?;
+
+        let user_ids: Vec<String> = result.take(0)?;
+        let Some(user_id) = user_ids.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let mut result = self.db
+            .query("SELECT * FROM type::thing('users', $user_id)")
+            .bind(("user_id", user_id))
+            .await?;
+
+        let users: Vec<User> = result.take(0)?;
+        Ok(users.into_iter().next())
+    }
+
+    pub async fn delete_session(&self, token_hash: &str) -> Result<()> {
+        self.db
+            .query("DELETE type::thing('sessions', $token_hash)")
+            .bind(("token_hash", token_hash))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_expired_sessions(&self) -> Result<()> {
+        self.db
+            .query("DELETE sessions WHERE expires_at <= time::now()")
+            .await?;
+        Ok(())
+    }
+
+    /// A cached grading stored under `key` that has not yet expired.
+    pub async fn get_cached_grading(&self, key: &str) -> Result<Option<QuestionFeedback>> {
+        let mut result = self.db
+            .query("SELECT VALUE feedback FROM type::thing('grading_cache', $key) WHERE expires_at > time::now()")
+            .bind(("key", key))
+            .await?;
+
+        let feedback: Vec<QuestionFeedback> = result.take(0)?;
+        Ok(feedback.into_iter().next())
+    }
+
+    /// Stores a grading under `key`, replacing any earlier one.
+    pub async fn store_cached_grading(
+        &self,
+        key: &str,
+        feedback: &QuestionFeedback,
+        expires_at: DateTime<Utc>,
+    ) -> Result<()> {
+        self.db
+            .query(
+                "UPDATE type::thing('grading_cache', $key) CONTENT { question_id: $question_id, \
+                 feedback: $feedback, expires_at: <datetime> $expires_at, created_at: time::now() }",
+            )
+            .bind(("key", key))
+            .bind(("question_id", &feedback.question_id))
+            .bind(("feedback", feedback))
+            .bind(("expires_at", expires_at))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_expired_cached_gradings(&self) -> Result<()> {
+        self.db
+            .query("DELETE grading_cache WHERE expires_at <= time::now()")
+            .await?;
+        Ok(())
+    }
+
     pub async fn health_check(&self) -> Result<()> {
         // Simple health check - try to query the database
         let _: Vec<surrealdb::sql::Value> = self.db
@@ -0,0 +1,137 @@
+use anyhow::Result;
+use std::io::{Seek, Write};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    models::{AuthUser, ExamDefinition, GradingStatus, Submission},
+    services::pdf::PDFService,
+};
+
+/// A submission paired with the student account that made it, if known.
+pub struct ExportEntry {
+    pub submission: Submission,
+    pub student: Option<AuthUser>,
+}
+
+/// Writes a ZIP holding `gradebook.csv` and one results PDF per graded
+/// submission under `pdfs/` into `writer`, rendering one PDF at a time.
+/// Rendering is synchronous, so call this from a blocking thread.
+pub fn write_results_archive<W: Write + Seek>(
+    writer: W,
+    exam: &ExamDefinition,
+    entries: &[ExportEntry],
+    pdf: &PDFService,
+) -> Result<W> {
+    let mut zip = ZipWriter::new(writer);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    // PDF streams are already compressed
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    zip.start_file("gradebook.csv", deflated)?;
+    zip.write_all(&gradebook_csv(exam, entries)?)?;
+
+    for entry in entries.iter().filter(|e| e.submission.results.is_some()) {
+        let code = &entry.submission.submission_code;
+        match pdf.render_results_pdf(&entry.submission, exam) {
+            Ok(bytes) => {
+                zip.start_file(format!("pdfs/{}.pdf", file_name_safe(code)), stored)?;
+                zip.write_all(&bytes)?;
+            }
+            // One bad report should not cost the teacher the whole export
+            Err(e) => tracing::error!("Skipping PDF for {} in export: {}", code, e),
+        }
+    }
+
+    Ok(zip.finish()?)
+}
+
+/// One row per submission: per-question scores in exam order, section totals,
+/// the overall total with its HSC band, and the model's per-question band estimates.
+pub fn gradebook_csv(exam: &ExamDefinition, entries: &[ExportEntry]) -> Result<Vec<u8>> {
+    let mut csv = csv::Writer::from_writer(Vec::new());
+
+    let mut header: Vec<String> = ["Submission Code", "Username", "Student Name", "Class", "Status"]
+        .iter()
+        .map(|h| h.to_string())
+        .collect();
+    header.extend(exam.questions().map(|q| q.id.clone()));
+    header.extend(exam.sections.iter().map(|s| s.name.clone()));
+    header.extend(["Total", "Max", "Percentage", "Aligned Mark", "Band", "Band Estimates"].iter().map(|h| h.to_string()));
+    csv.write_record(&header)?;
+
+    for entry in entries {
+        let submission = &entry.submission;
+        let student = entry.student.as_ref();
+        let mut row = vec![
+            submission.submission_code.clone(),
+            student.map(|s| s.username.clone()).unwrap_or_default(),
+            student.map(|s| s.display_name.clone()).unwrap_or_default(),
+            student.and_then(|s| s.class_name.clone()).unwrap_or_default(),
+            status_label(&submission.grading_status).to_string(),
+        ];
+
+        match &submission.results {
+            Some(results) => {
+                row.extend(exam.questions().map(|q| {
+                    results
+                        .question_feedback
+                        .values()
+                        .find(|f| f.question_id == q.id)
+                        .map(|f| f.score.to_string())
+                        .unwrap_or_default()
+                }));
+                row.extend(exam.sections.iter().map(|s| {
+                    results
+                        .section_scores
+                        .get(&s.name)
+                        .map(|score| score.score.to_string())
+                        .unwrap_or_default()
+                }));
+                row.push(results.total_score.to_string());
+                row.push(results.max_score.to_string());
+                row.push(if results.max_score > 0.0 {
+                    format!("{:.1}", results.total_score / results.max_score * 100.0)
+                } else {
+                    String::new()
+                });
+                match &results.band {
+                    Some(band) => {
+                        row.push(format!("{:.1}", band.aligned_mark));
+                        row.push(band.display_name());
+                    }
+                    None => row.extend([String::new(), String::new()]),
+                }
+                row.push(
+                    results
+                        .feedback_in_exam_order(exam)
+                        .into_iter()
+                        .filter_map(|(key, f)| f.band_estimate.as_ref().map(|band| format!("{}: {}", f.label(key), band)))
+                        .collect::<Vec<_>>()
+                        .join("; "),
+                );
+            }
+            None => row.resize(header.len(), String::new()),
+        }
+
+        csv.write_record(&row)?;
+    }
+
+    csv.into_inner().map_err(|e| anyhow::anyhow!("Failed to write gradebook: {}", e))
+}
+
+fn status_label(status: &GradingStatus) -> &'static str {
+    match status {
+        GradingStatus::Pending => "Pending",
+        GradingStatus::InProgress => "In Progress",
+        GradingStatus::Completed => "Completed",
+        GradingStatus::PartiallyCompleted => "Partially Completed",
+        GradingStatus::Failed { .. } => "Failed",
+    }
+}
+
+/// Keeps archive entry names to characters that are safe on every file system.
+pub fn file_name_safe(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
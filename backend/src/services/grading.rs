@@ -0,0 +1,536 @@
+use anyhow::Result;
+use chrono::Utc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{
+    models::{
+        BandResult, ExamDefinition, GradingResults, GradingScope, GradingStatus, MultipleChoiceResult,
+        QuestionFeedback, QuestionKind, QuestionStatus, SectionScore,
+    },
+    services::{
+        ai::AIService,
+        database::DatabaseService,
+        progress::{ProgressHub, ProgressReporter},
+    },
+};
+
+/// Runs the full grading pipeline for a stored submission.
+#[derive(Clone)]
+pub struct GradingService {
+    database: Arc<DatabaseService>,
+    ai_service: Arc<AIService>,
+    progress: Arc<ProgressHub>,
+}
+
+impl GradingService {
+    pub fn new(database: Arc<DatabaseService>, ai_service: Arc<AIService>, progress: Arc<ProgressHub>) -> Self {
+        Self { database, ai_service, progress }
+    }
+
+    /// Reports progress for a submission to anyone watching it.
+    pub fn progress(&self, submission_code: &str) -> ProgressReporter {
+        self.progress.reporter(submission_code)
+    }
+
+    pub async fn process_submission(
+        &self,
+        submission_code: &str,
+        scope: GradingScope,
+        bypass_cache: bool,
+    ) -> Result<()> {
+        // Update status to in progress
+        self.database.update_grading_status(submission_code, GradingStatus::InProgress).await?;
+
+        // Get submission
+        let submission = self.database.get_submission(submission_code).await?
+            .ok_or_else(|| anyhow::anyhow!("Submission not found"))?;
+
+        let exam = self.database.get_exam(&submission.exam_id).await?
+            .ok_or_else(|| anyhow::anyhow!("Exam {} not found", submission.exam_id))?;
+        let progress = self.progress(submission_code);
+
+        // Grade multiple choice automatically
+        let mc_results = grade_multiple_choice(&exam, &submission.responses.multiple_choice);
+
+        // Grade extended responses with AI
+        let responses = &submission.responses.extended_response;
+        let ai_results = match (scope, &submission.results) {
+            (GradingScope::FailedQuestions, Some(previous)) => {
+                let previous: Vec<QuestionFeedback> = previous
+                    .question_feedback
+                    .values()
+                    .filter(|f| !f.question_id.is_empty())
+                    .cloned()
+                    .collect();
+                let failed: HashSet<String> = previous
+                    .iter()
+                    .filter(|f| f.is_failed())
+                    .map(|f| f.question_id.clone())
+                    .collect();
+
+                let regraded = self
+                    .ai_service
+                    .grade_extended_responses(&exam, responses, Some(&failed), bypass_cache, &progress)
+                    .await;
+                merge_regraded(&exam, previous, regraded)
+            }
+            _ => {
+                self.ai_service
+                    .grade_extended_responses(&exam, responses, None, bypass_cache, &progress)
+                    .await
+            }
+        };
+
+        // Combine results; totals, section scores and band are filled in below
+        let mut grading_results = GradingResults {
+            total_score: 0.0,
+            max_score: 0.0,
+            section_scores: HashMap::new(),
+            question_feedback: create_question_feedback(&mc_results, &ai_results),
+            overall_feedback: String::new(),
+            ai_provider_used: if ai_results.is_empty() { String::new() } else { "AI Service".to_string() },
+            graded_at: Utc::now(),
+            band: None,
+            prompt_version: (!ai_results.is_empty()).then(|| self.ai_service.prompt_version().to_string()),
+            // Keep the audit trail even when the submission is re-graded
+            moderation_history: submission.results.as_ref()
+                .map(|r| r.moderation_history.clone())
+                .unwrap_or_default(),
+        };
+
+        // A teacher's mark stands over any re-grade
+        apply_overrides(&mut grading_results);
+        recompute_totals(&exam, &mut grading_results);
+
+        let status = if grading_results.question_feedback.values().any(|f| f.is_failed()) {
+            GradingStatus::PartiallyCompleted
+        } else {
+            GradingStatus::Completed
+        };
+
+        // Store results
+        self.database.store_grading_results(submission_code, &grading_results).await?;
+        self.database.update_grading_status(submission_code, status.clone()).await?;
+        progress.finished(&status);
+
+        Ok(())
+    }
+}
+
+/// Replaces previously failed questions with their re-graded feedback, keeping
+/// exam order and leaving successfully graded questions untouched.
+fn merge_regraded(
+    exam: &ExamDefinition,
+    previous: Vec<QuestionFeedback>,
+    regraded: Vec<QuestionFeedback>,
+) -> Vec<QuestionFeedback> {
+    let mut by_id: HashMap<String, QuestionFeedback> = previous
+        .into_iter()
+        .chain(regraded)
+        .map(|f| (f.question_id.clone(), f))
+        .collect();
+
+    exam.questions()
+        .filter(|q| q.kind != QuestionKind::MultipleChoice)
+        .filter_map(|q| by_id.remove(&q.id))
+        .collect()
+}
+
+/// Replays the teacher overrides onto freshly graded feedback, oldest first as
+/// they were made, so each question keeps its latest mark and the last
+/// feedback a teacher wrote for it.
+fn apply_overrides(results: &mut GradingResults) {
+    for record in &results.moderation_history {
+        let Some(feedback) = results
+            .question_feedback
+            .values_mut()
+            .find(|f| f.question_id == record.question_id)
+        else {
+            continue;
+        };
+        feedback.score = record.score;
+        if let Some(text) = &record.feedback {
+            feedback.feedback = text.clone();
+        }
+        feedback.status = QuestionStatus::Graded;
+        feedback.moderated = true;
+    }
+}
+
+/// Recomputes section scores, totals and overall feedback from the current
+/// question feedback, e.g. after a teacher override.
+pub fn recompute_totals(exam: &ExamDefinition, results: &mut GradingResults) {
+    let mut sections = create_section_scores(exam, &results.question_feedback);
+
+    // Results graded before per-question feedback hold a single aggregate entry
+    // for the multiple-choice questions instead of one per question
+    let legacy_aggregate = results.question_feedback.values().find(|f| f.question_id.is_empty());
+    let graded_mc = results.question_feedback.values().any(|f| f.multiple_choice.is_some());
+    let mc_section = exam.sections.iter().find(|s| s.questions().any(|q| q.kind == QuestionKind::MultipleChoice));
+    if let (Some(aggregate), false, Some(section)) = (legacy_aggregate, graded_mc, mc_section) {
+        if let Some(score) = sections.get_mut(&section.name) {
+            score.score += aggregate.score;
+            score.max_score += aggregate.max_score;
+            score.feedback = aggregate.feedback.clone();
+        }
+    }
+
+    results.section_scores = sections;
+    results.total_score = results.section_scores.values().map(|s| s.score).sum();
+    results.max_score = results.section_scores.values().map(|s| s.max_score).sum();
+    let band = exam.band_for(results.total_score, results.max_score);
+    results.overall_feedback = generate_overall_feedback(&band);
+    results.band = Some(band);
+}
+
+/// Marks each multiple-choice question against the exam key.
+fn grade_multiple_choice(exam: &ExamDefinition, responses: &HashMap<String, String>) -> Vec<QuestionFeedback> {
+    exam.questions()
+        .filter(|q| q.kind == QuestionKind::MultipleChoice)
+        .map(|question| {
+            let correct_answer = question.answer.clone().unwrap_or_default();
+            let student_answer = responses
+                .get(&question.id)
+                .map(|a| a.trim().to_uppercase())
+                .filter(|a| !a.is_empty());
+            let correct = student_answer.as_deref() == Some(correct_answer.as_str());
+
+            let feedback = match &student_answer {
+                _ if correct => "Correct.".to_string(),
+                Some(answer) => format!("You answered {}; the correct answer is {}.", answer, correct_answer),
+                None => format!("No answer was given; the correct answer is {}.", correct_answer),
+            };
+
+            QuestionFeedback {
+                question_id: question.id.clone(),
+                score: if correct { question.marks } else { 0.0 },
+                max_score: question.marks,
+                feedback,
+                multiple_choice: Some(MultipleChoiceResult {
+                    student_answer,
+                    correct_answer,
+                    correct,
+                    explanation: question.explanation.clone(),
+                    syllabus_point: question.syllabus_point.clone(),
+                }),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+fn multiple_choice_section(mc_results: &[QuestionFeedback]) -> SectionScore {
+    let correct = mc_results
+        .iter()
+        .filter(|f| f.multiple_choice.as_ref().is_some_and(|mc| mc.correct))
+        .count();
+
+    SectionScore {
+        score: mc_results.iter().map(|f| f.score).sum(),
+        max_score: mc_results.iter().map(|f| f.max_score).sum(),
+        feedback: format!("Multiple choice: {}/{} correct", correct, mc_results.len()),
+    }
+}
+
+/// Scores each exam section from the feedback for its questions and parts,
+/// keyed by section name.
+fn create_section_scores(
+    exam: &ExamDefinition,
+    question_feedback: &HashMap<String, QuestionFeedback>,
+) -> HashMap<String, SectionScore> {
+    let by_id: HashMap<&str, &QuestionFeedback> = question_feedback
+        .values()
+        .map(|f| (f.question_id.as_str(), f))
+        .collect();
+
+    exam.sections
+        .iter()
+        .map(|section| {
+            let results: Vec<QuestionFeedback> = section
+                .questions()
+                .filter_map(|q| by_id.get(q.id.as_str()).map(|f| (*f).clone()))
+                .collect();
+            let (mc, extended): (Vec<QuestionFeedback>, Vec<QuestionFeedback>) =
+                results.into_iter().partition(|f| f.multiple_choice.is_some());
+            let mc_score = multiple_choice_section(&mc);
+            let extended_score = extended.iter().map(|r| r.score).sum::<f64>();
+            let extended_max = extended.iter().map(|r| r.max_score).sum::<f64>();
+            let extended_feedback = format!("Extended response: {:.1}/{:.1}", extended_score, extended_max);
+
+            let score = SectionScore {
+                score: mc_score.score + extended_score,
+                max_score: mc_score.max_score + extended_max,
+                feedback: match (mc.is_empty(), extended.is_empty()) {
+                    (false, true) => mc_score.feedback,
+                    (false, false) => format!("{}; {}", mc_score.feedback, extended_feedback),
+                    (true, _) => extended_feedback,
+                },
+            };
+            (section.name.clone(), score)
+        })
+        .collect()
+}
+
+/// Keys every question's feedback by its exam question id, e.g. `q1` or `q25a`.
+fn create_question_feedback(mc_results: &[QuestionFeedback], ai_results: &[QuestionFeedback]) -> HashMap<String, QuestionFeedback> {
+    mc_results
+        .iter()
+        .chain(ai_results)
+        .map(|result| (result.question_id.clone(), result.clone()))
+        .collect()
+}
+
+fn generate_overall_feedback(band: &BandResult) -> String {
+    let summary = match band.band.as_str() {
+        "6" | "E4" => "Excellent performance demonstrating comprehensive understanding of chemistry concepts.",
+        "5" | "E3" => "Strong performance with good understanding of most concepts.",
+        "4" => "Good performance with solid understanding of key concepts.",
+        "3" | "E2" => "Satisfactory performance with basic understanding demonstrated.",
+        _ => "Performance indicates need for additional study and practice.",
+    };
+
+    format!("{}: {}", band.summary(), summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ExamResponses, MarkOverride, Submission};
+    use serde_json::json;
+
+    fn feedback(question_id: &str, score: f64, status: QuestionStatus) -> (String, QuestionFeedback) {
+        let feedback = QuestionFeedback {
+            question_id: question_id.to_string(),
+            score,
+            max_score: 4.0,
+            feedback: format!("Model feedback for {}", question_id),
+            status,
+            ..Default::default()
+        };
+        (question_id.to_string(), feedback)
+    }
+
+    fn record(question_id: &str, score: f64, feedback: Option<&str>) -> MarkOverride {
+        MarkOverride {
+            question_id: question_id.to_string(),
+            previous_score: 0.0,
+            previous_feedback: String::new(),
+            original_score: None,
+            original_feedback: None,
+            score,
+            feedback: feedback.map(str::to_string),
+            reason: "Moderated".to_string(),
+            teacher: "teacher".to_string(),
+            overridden_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn regrading_keeps_teacher_overrides() {
+        let mut results = GradingResults {
+            total_score: 0.0,
+            max_score: 0.0,
+            section_scores: HashMap::new(),
+            question_feedback: [
+                feedback("q22", 1.0, QuestionStatus::Graded),
+                feedback("q23", 0.0, QuestionStatus::Failed { error: "timeout".to_string() }),
+                feedback("q24", 2.0, QuestionStatus::Graded),
+            ]
+            .into_iter()
+            .collect(),
+            overall_feedback: String::new(),
+            ai_provider_used: String::new(),
+            graded_at: Utc::now(),
+            band: None,
+            prompt_version: None,
+            moderation_history: vec![
+                record("q22", 2.0, Some("First look")),
+                record("q23", 3.0, None),
+                record("q22", 4.0, None),
+            ],
+        };
+
+        apply_overrides(&mut results);
+
+        let q22 = &results.question_feedback["q22"];
+        assert_eq!(q22.score, 4.0);
+        assert_eq!(q22.feedback, "First look");
+        assert!(q22.moderated);
+        let q23 = &results.question_feedback["q23"];
+        assert_eq!((q23.score, &q23.status), (3.0, &QuestionStatus::Graded));
+        let q24 = &results.question_feedback["q24"];
+        assert_eq!(q24.score, 2.0);
+        assert!(!q24.moderated);
+    }
+
+    const SECTIONED_EXAM: &str = r#"
+id = "sections"
+title = "Sections"
+
+[[sections]]
+name = "Section I"
+
+[[sections.questions]]
+id = "q1"
+kind = "multiple_choice"
+marks = 1
+answer = "A"
+
+[[sections.questions]]
+id = "q2"
+kind = "multiple_choice"
+marks = 1
+answer = "B"
+
+[[sections]]
+name = "Section II"
+
+[[sections.questions]]
+id = "q3"
+kind = "multiple_choice"
+marks = 1
+answer = "C"
+
+[[sections.questions]]
+id = "q4"
+marks = 4
+
+[[sections]]
+name = "Section III"
+
+[[sections.questions]]
+id = "q5"
+kind = "short_answer"
+marks = 4
+
+[[sections.questions]]
+id = "q6"
+marks = 8
+
+[[sections.questions.parts]]
+id = "q6a"
+marks = 4
+
+[[sections.questions.parts]]
+id = "q6b"
+marks = 4
+"#;
+
+    #[test]
+    fn scores_every_section_including_mixed_ones() {
+        let exam: ExamDefinition = toml::from_str(SECTIONED_EXAM).unwrap();
+        let answers: HashMap<String, String> = [("q1", "A"), ("q2", "D"), ("q3", "C")]
+            .into_iter()
+            .map(|(id, answer)| (id.to_string(), answer.to_string()))
+            .collect();
+        let mc_results = grade_multiple_choice(&exam, &answers);
+        let written: Vec<QuestionFeedback> = [("q4", 3.0), ("q5", 2.0), ("q6a", 4.0), ("q6b", 1.0)]
+            .into_iter()
+            .map(|(id, score)| feedback(id, score, QuestionStatus::Graded).1)
+            .collect();
+
+        let mut results = GradingResults {
+            total_score: 0.0,
+            max_score: 0.0,
+            section_scores: HashMap::new(),
+            question_feedback: create_question_feedback(&mc_results, &written),
+            overall_feedback: String::new(),
+            ai_provider_used: String::new(),
+            graded_at: Utc::now(),
+            band: None,
+            prompt_version: None,
+            moderation_history: Vec::new(),
+        };
+        recompute_totals(&exam, &mut results);
+
+        let section = |name: &str| {
+            let score = &results.section_scores[name];
+            (score.score, score.max_score, score.feedback.as_str())
+        };
+        assert_eq!(results.section_scores.len(), 3);
+        assert_eq!(section("Section I"), (1.0, 2.0, "Multiple choice: 1/2 correct"));
+        assert_eq!(
+            section("Section II"),
+            (4.0, 5.0, "Multiple choice: 1/1 correct; Extended response: 3.0/4.0")
+        );
+        assert_eq!(section("Section III"), (7.0, 12.0, "Extended response: 7.0/12.0"));
+        assert_eq!((results.total_score, results.max_score), (12.0, 19.0));
+        assert!(results.band.is_some());
+    }
+
+    #[tokio::test]
+    async fn grades_a_stored_submission_end_to_end() {
+        let database = Arc::new(DatabaseService::new("memory").await.unwrap());
+        database.initialize_schema().await.unwrap();
+        let exam: ExamDefinition = toml::from_str(SECTIONED_EXAM).unwrap();
+        database.store_exam(&exam).await.unwrap();
+
+        let submission = Submission {
+            id: uuid::Uuid::new_v4(),
+            submission_code: "MOCK-E2E".to_string(),
+            exam_id: exam.id.clone(),
+            student_id: None,
+            responses: ExamResponses {
+                multiple_choice: HashMap::from([
+                    ("q1".to_string(), "a".to_string()),
+                    ("q2".to_string(), "D".to_string()),
+                    ("q3".to_string(), "C".to_string()),
+                ]),
+                extended_response: HashMap::from([
+                    ("q4".to_string(), json!("Ethanol hydrogen bonds with water.")),
+                    ("q5".to_string(), json!("butanal")),
+                    ("q6".to_string(), json!({"a": "n = m/M = 0.0594 mol", "b": ""})),
+                ]),
+                time_taken_minutes: 90.0,
+            },
+            submitted_at: Utc::now(),
+            grading_status: GradingStatus::Pending,
+            results: None,
+        };
+        database.store_submission(&submission).await.unwrap();
+
+        let reply = |score: f64| {
+            format!(
+                r#"{{"score": {}, "criteria": [], "feedback": "Scripted.", "strengths": [], "improvements": [], "band_estimate": null}}"#,
+                score
+            )
+        };
+        let (q4, q6a, other) = (reply(3.0), reply(4.0), reply(1.0));
+        let ai_service = AIService::mock(&[("q4", &q4), ("q6a", &q6a), ("*", &other)]);
+        let grading = GradingService::new(Arc::clone(&database), Arc::new(ai_service), Arc::new(ProgressHub::new()));
+
+        grading.process_submission("MOCK-E2E", GradingScope::Full, false).await.unwrap();
+
+        let stored = database.get_submission("MOCK-E2E").await.unwrap().unwrap();
+        assert!(matches!(stored.grading_status, GradingStatus::Completed));
+        let results = stored.results.unwrap();
+
+        let score = |id: &str| results.question_feedback[id].score;
+        assert_eq!(
+            ["q1", "q2", "q3", "q4", "q5", "q6a", "q6b"].map(score),
+            [1.0, 0.0, 1.0, 3.0, 1.0, 4.0, 0.0]
+        );
+        assert_eq!(
+            results.question_feedback["q2"].feedback,
+            "You answered D; the correct answer is B."
+        );
+        assert_eq!(
+            results.question_feedback["q6b"].feedback,
+            "No response was submitted for this question."
+        );
+
+        let section = |name: &str| {
+            let score = &results.section_scores[name];
+            (score.score, score.max_score)
+        };
+        assert_eq!(section("Section I"), (1.0, 2.0));
+        assert_eq!(section("Section II"), (4.0, 5.0));
+        assert_eq!(section("Section III"), (5.0, 12.0));
+        assert_eq!((results.total_score, results.max_score), (10.0, 19.0));
+        assert_eq!(results.band, Some(exam.band_for(10.0, 19.0)));
+        assert_eq!(results.prompt_version.as_deref(), Some("v4"));
+    }
+}
@@ -0,0 +1,114 @@
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+use crate::{
+    config::Config,
+    models::{exam::QuestionDefinition, QuestionFeedback},
+    services::database::DatabaseService,
+};
+
+/// Model gradings stored in the `grading_cache` table under a hash of
+/// everything they depend on, so re-grading a submission or grading an
+/// identical response again reuses the grading instead of making a new AI call.
+#[derive(Clone)]
+pub struct GradingCache {
+    /// The database and how long a grading is reused; `None` when the cache is
+    /// turned off.
+    store: Option<(Arc<DatabaseService>, Duration)>,
+}
+
+/// What a model grading depends on besides the provider and prompt version.
+pub struct CacheKey<'a> {
+    pub subject: &'a str,
+    pub guidelines: &'a str,
+    pub question: &'a QuestionDefinition,
+    pub parent: Option<&'a QuestionDefinition>,
+    pub response: &'a Value,
+}
+
+impl GradingCache {
+    pub fn new(database: Arc<DatabaseService>, config: &Config) -> Self {
+        if config.grading_cache_ttl_hours == 0 {
+            return Self::disabled();
+        }
+        Self {
+            store: Some((database, Duration::hours(config.grading_cache_ttl_hours as i64))),
+        }
+    }
+
+    /// A cache that never holds a grading, so every response goes to the model.
+    pub fn disabled() -> Self {
+        Self { store: None }
+    }
+
+    /// An unexpired grading stored under `key`. The cache never stops a
+    /// response being graded, so a failed lookup is logged and treated as a miss.
+    pub async fn get(&self, key: &str) -> Option<QuestionFeedback> {
+        let (database, _) = self.store.as_ref()?;
+        match database.get_cached_grading(key).await {
+            Ok(Some(feedback)) => {
+                tracing::info!("Reusing cached grading of {}", feedback.question_id);
+                Some(feedback)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                tracing::warn!("Could not read cached grading {}: {}", key, e);
+                None
+            }
+        }
+    }
+
+    pub async fn store(&self, key: &str, feedback: &QuestionFeedback) {
+        let Some((database, ttl)) = &self.store else {
+            return;
+        };
+        if let Err(e) = database.store_cached_grading(key, feedback, Utc::now() + *ttl).await {
+            tracing::warn!("Could not cache grading of {}: {}", feedback.question_id, e);
+        }
+    }
+}
+
+impl CacheKey<'_> {
+    /// Hash of the question, rubric version, prompt version, model and
+    /// normalised response. The exam has no rubric version of its own, so the
+    /// question, its stem and the guidelines are hashed in its place: editing
+    /// the criteria, marks or answer keys stops earlier gradings being reused.
+    pub fn digest(&self, prompt_version: &str, model: &str) -> String {
+        let rubric = json!({
+            "subject": self.subject,
+            "guidelines": self.guidelines,
+            "question": self.question,
+            "parent": self.parent,
+        });
+        sha256(&json!({
+            "question_id": self.question.id,
+            "rubric": sha256(&rubric),
+            "prompt_version": prompt_version,
+            "model": model,
+            "response": normalise(self.response),
+        }))
+    }
+}
+
+fn sha256(value: &Value) -> String {
+    hex::encode(Sha256::digest(value.to_string().as_bytes()))
+}
+
+/// Trims and collapses the whitespace in every answer, so responses that differ
+/// only in spacing or line breaks share a grading. Case is kept, as it matters
+/// in formulas such as `CO` and `Co`.
+fn normalise(response: &Value) -> Value {
+    match response {
+        Value::String(text) => Value::String(text.split_whitespace().collect::<Vec<_>>().join(" ")),
+        Value::Array(items) => Value::Array(items.iter().map(normalise).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, value)| (name.clone(), normalise(value)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
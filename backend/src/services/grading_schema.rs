@@ -0,0 +1,268 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+use crate::models::{exam::QuestionDefinition, CriterionResult, QuestionFeedback, QuestionStatus};
+
+/// Name given to the schema in structured-output requests.
+pub const SCHEMA_NAME: &str = "question_grading";
+
+/// The grading object the model must return, mirroring the model-supplied
+/// fields of [`QuestionFeedback`]. Unknown fields are rejected.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ModelGrading {
+    score: f64,
+    criteria: Vec<CriterionVerdict>,
+    feedback: String,
+    strengths: Vec<String>,
+    improvements: Vec<String>,
+    band_estimate: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CriterionVerdict {
+    id: String,
+    awarded: bool,
+    comment: String,
+}
+
+/// JSON schema for grading `question`, in the subset accepted by strict
+/// structured-output modes: every property required, no extra properties.
+pub fn response_schema(question: &QuestionDefinition) -> Value {
+    let criterion_ids: Vec<String> = (1..=question.criteria.len()).map(|i| format!("C{}", i)).collect();
+    let criterion_id = if criterion_ids.is_empty() {
+        json!({ "type": "string" })
+    } else {
+        json!({ "type": "string", "enum": criterion_ids })
+    };
+
+    json!({
+        "type": "object",
+        "properties": {
+            "score": {
+                "type": "number",
+                "description": format!("Marks awarded, from 0 to {}", question.marks)
+            },
+            "criteria": {
+                "type": "array",
+                "description": "A verdict for each marking criterion",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "id": criterion_id,
+                        "awarded": { "type": "boolean" },
+                        "comment": { "type": "string" }
+                    },
+                    "required": ["id", "awarded", "comment"],
+                    "additionalProperties": false
+                }
+            },
+            "feedback": { "type": "string" },
+            "strengths": { "type": "array", "items": { "type": "string" } },
+            "improvements": { "type": "array", "items": { "type": "string" } },
+            "band_estimate": { "type": ["string", "null"] }
+        },
+        "required": ["score", "criteria", "feedback", "strengths", "improvements", "band_estimate"],
+        "additionalProperties": false
+    })
+}
+
+/// Parses and validates model output against the schema for `question`,
+/// returning every problem found so they can be sent back to the model.
+pub fn parse_response(content: &str, question: &QuestionDefinition) -> Result<QuestionFeedback, Vec<String>> {
+    let grading: ModelGrading =
+        serde_json::from_str(strip_code_fence(content)).map_err(|e| vec![format!("Invalid grading JSON: {}", e)])?;
+
+    let mut problems = Vec::new();
+    if !grading.score.is_finite() || grading.score < 0.0 || grading.score > question.marks {
+        problems.push(format!("score {} must be between 0 and {}", grading.score, question.marks));
+    }
+    if grading.feedback.trim().is_empty() {
+        problems.push("feedback must not be empty".to_string());
+    }
+
+    let mut seen = HashSet::new();
+    for verdict in &grading.criteria {
+        let index = verdict
+            .id
+            .trim()
+            .to_uppercase()
+            .strip_prefix('C')
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|n| (1..=question.criteria.len()).contains(n));
+        match index {
+            Some(n) if !seen.insert(n) => problems.push(format!("criterion {} is listed more than once", verdict.id)),
+            Some(_) => {}
+            None => problems.push(format!(
+                "criterion id '{}' is not one of C1-C{}",
+                verdict.id,
+                question.criteria.len()
+            )),
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+
+    // Criteria the model did not mention are recorded as not awarded
+    let criteria = question
+        .criteria
+        .iter()
+        .enumerate()
+        .map(|(i, criterion)| {
+            let id = format!("C{}", i + 1);
+            let verdict = grading.criteria.iter().find(|v| v.id.trim().eq_ignore_ascii_case(&id));
+            CriterionResult {
+                description: criterion.description.clone(),
+                marks: criterion.marks,
+                awarded: verdict.is_some_and(|v| v.awarded),
+                comment: verdict.map(|v| v.comment.clone()).filter(|c| !c.is_empty()),
+            }
+        })
+        .collect();
+
+    Ok(QuestionFeedback {
+        question_id: question.id.clone(),
+        status: QuestionStatus::Graded,
+        score: grading.score,
+        max_score: question.marks,
+        feedback: grading.feedback,
+        strengths: grading.strengths,
+        improvements: grading.improvements,
+        band_estimate: grading.band_estimate.filter(|b| !b.trim().is_empty()),
+        criteria,
+        ..Default::default()
+    })
+}
+
+/// Models without a structured-output mode often wrap JSON in a Markdown fence.
+fn strip_code_fence(content: &str) -> &str {
+    let trimmed = content.trim();
+    trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.strip_suffix("```"))
+        .map(str::trim)
+        .unwrap_or(trimmed)
+}
+
+/// A follow-up prompt asking the model to correct invalid output.
+pub fn repair_prompt(original_prompt: &str, invalid_output: &str, problems: &[String]) -> String {
+    format!(
+        "{}\n\n\
+        Your previous reply could not be accepted:\n{}\n\n\
+        Previous reply:\n{}\n\n\
+        Reply again with only a JSON object that follows the required structure exactly.",
+        original_prompt,
+        problems.iter().map(|p| format!("- {}", p)).collect::<Vec<_>>().join("\n"),
+        invalid_output
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn question() -> QuestionDefinition {
+        toml::from_str(
+            r#"
+id = "q22"
+marks = 3
+
+[[criteria]]
+marks = 2
+description = "Explains hydrogen bonding"
+
+[[criteria]]
+marks = 1
+description = "Mentions polarity"
+"#,
+        )
+        .unwrap()
+    }
+
+    fn problems(content: &str) -> Vec<String> {
+        parse_response(content, &question()).unwrap_err()
+    }
+
+    #[test]
+    fn accepts_a_conforming_reply() {
+        let feedback = parse_response(
+            "```json\n{\"score\": 2, \"criteria\": [{\"id\": \"c1\", \"awarded\": true, \"comment\": \"\"}], \
+             \"feedback\": \"Good.\", \"strengths\": [\"Clear\"], \"improvements\": [], \"band_estimate\": \" \"}\n```",
+            &question(),
+        )
+        .unwrap();
+
+        assert_eq!((feedback.score, feedback.max_score), (2.0, 3.0));
+        assert_eq!(feedback.band_estimate, None);
+        // Criteria the model left out are recorded as not awarded
+        let awarded: Vec<(bool, Option<&str>)> =
+            feedback.criteria.iter().map(|c| (c.awarded, c.comment.as_deref())).collect();
+        assert_eq!(awarded, [(true, None), (false, None)]);
+    }
+
+    #[test]
+    fn rejects_missing_fields_and_wrong_types() {
+        let missing =
+            problems(r#"{"score": 2, "criteria": [], "strengths": [], "improvements": [], "band_estimate": null}"#);
+        assert_eq!(missing.len(), 1);
+        assert!(missing[0].starts_with("Invalid grading JSON: missing field `feedback`"), "{:?}", missing);
+
+        let wrong_type = problems(
+            r#"{"score": "two", "criteria": [], "feedback": "Good.", "strengths": [], "improvements": [], "band_estimate": null}"#,
+        );
+        assert!(
+            wrong_type[0].starts_with("Invalid grading JSON: invalid type: string \"two\", expected f64"),
+            "{:?}",
+            wrong_type
+        );
+
+        let unknown = problems(
+            r#"{"score": 2, "marks": 2, "criteria": [], "feedback": "Good.", "strengths": [], "improvements": [], "band_estimate": null}"#,
+        );
+        assert!(unknown[0].starts_with("Invalid grading JSON: unknown field `marks`"), "{:?}", unknown);
+    }
+
+    #[test]
+    fn lists_every_problem_with_scores_and_criteria() {
+        let problems = problems(
+            r#"{"score": 4, "criteria": [
+                {"id": "C1", "awarded": true, "comment": ""},
+                {"id": "C1", "awarded": false, "comment": ""},
+                {"id": "C3", "awarded": true, "comment": ""},
+                {"id": "polarity", "awarded": true, "comment": ""}
+            ], "feedback": " ", "strengths": [], "improvements": [], "band_estimate": null}"#,
+        );
+        assert_eq!(
+            problems,
+            [
+                "score 4 must be between 0 and 3",
+                "feedback must not be empty",
+                "criterion C1 is listed more than once",
+                "criterion id 'C3' is not one of C1-C2",
+                "criterion id 'polarity' is not one of C1-C2",
+            ]
+        );
+    }
+
+    #[test]
+    fn limits_criterion_ids_in_the_schema() {
+        let schema = response_schema(&question());
+        let id = &schema["properties"]["criteria"]["items"]["properties"]["id"];
+        assert_eq!(id["enum"], json!(["C1", "C2"]));
+        assert_eq!(schema["additionalProperties"], json!(false));
+    }
+
+    #[test]
+    fn repair_prompt_lists_the_problems_and_reply() {
+        let prompt = repair_prompt("Grade q22.", "{\"score\": 4}", &["score 4 must be between 0 and 3".to_string()]);
+        assert!(prompt.starts_with(
+            "Grade q22.\n\nYour previous reply could not be accepted:\n- score 4 must be between 0 and 3"
+        ));
+        assert!(prompt.contains("Previous reply:\n{\"score\": 4}"));
+    }
+}
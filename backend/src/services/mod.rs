@@ -1,4 +1,17 @@
 pub mod ai;
+pub mod analytics;
+pub mod auth;
+pub mod consensus;
 pub mod database;
+pub mod export;
+pub mod grading;
+pub mod grading_cache;
+pub mod grading_schema;
 pub mod pdf;
+pub mod progress;
+pub mod prompts;
+pub mod providers;
+pub mod queue;
+pub mod report;
+pub mod short_answer;
 pub mod storage;
@@ -1,246 +1,504 @@
-use anyhow::Result;
-use chrono::{DateTime, Utc};
-use std::collections::HashMap;
-use uuid::Uuid;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use printpdf::{
+    BuiltinFont, Color, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Point,
+    Rect, Rgb,
+};
+use std::{path::Path, sync::Arc};
 
-use crate::models::{GradingResults, Submission};
+use crate::{
+    config::Config,
+    models::{ExamDefinition, QuestionFeedback, QuestionStatus, Submission},
+    services::report::Branding,
+};
 
-pub struct PDFService;
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+const FOOTER_HEIGHT: f32 = 12.0;
+const CONTENT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+
+/// Millimetres per typographic point.
+const PT_TO_MM: f32 = 0.3528;
+/// Average glyph width as a fraction of the font size, used to wrap text.
+/// Slightly generous for DejaVu Sans so lines never overrun the margin.
+const AVERAGE_GLYPH_WIDTH: f32 = 0.55;
+const LINE_SPACING: f32 = 1.35;
+
+const BODY_SIZE: f32 = 10.0;
+const SMALL_SIZE: f32 = 8.5;
+
+/// Renders results reports as PDF in-process with `printpdf`.
+///
+/// The configured TrueType fonts are embedded in the document; if they cannot
+/// be read, the standard Helvetica fonts are used instead so a report is still
+/// produced, at the cost of characters outside Latin-1.
+#[derive(Clone)]
+pub struct PDFService {
+    fonts: Arc<FontData>,
+    branding: Branding,
+}
 
 impl PDFService {
-    pub fn new() -> Self {
-        Self
-    }
-
-    pub async fn generate_results_pdf(&self, submission: &Submission) -> Result<Vec<u8>> {
-        let results = submission.results.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No grading results available"))?;
-
-        let html_content = self.generate_html_report(submission, results)?;
-        
-        // Use wkhtmltopdf to convert HTML to PDF
-        let pdf_bytes = self.html_to_pdf(&html_content).await?;
-        
-        Ok(pdf_bytes)
-    }
-
-    fn generate_html_report(&self, submission: &Submission, results: &GradingResults) -> Result<String> {
-        let html = format!(r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <meta charset="UTF-8">
-    <title>HSC Chemistry Examination Results</title>
-    <style>
-        body {{
-            font-family: 'Times New Roman', serif;
-            margin: 40px;
-            line-height: 1.6;
-        }}
-        .header {{
-            text-align: center;
-            border-bottom: 2px solid #333;
-            padding-bottom: 20px;
-            margin-bottom: 30px;
-        }}
-        .score-summary {{
-            background-color: #f8f9fa;
-            padding: 20px;
-            border-radius: 8px;
-            margin-bottom: 30px;
-        }}
-        .question-feedback {{
-            margin-bottom: 25px;
-            padding: 15px;
-            border-left: 4px solid #007bff;
-            background-color: #f8f9fa;
-        }}
-        .score {{
-            font-weight: bold;
-            color: #28a745;
-        }}
-        .improvements {{
-            color: #dc3545;
-        }}
-        .strengths {{
-            color: #28a745;
-        }}
-        .footer {{
-            margin-top: 40px;
-            text-align: center;
-            font-size: 12px;
-            color: #666;
-        }}
-        @media print {{
-            body {{ margin: 20px; }}
-        }}
-    </style>
-</head>
-<body>
-    <div class="header">
-        <h1>NSW HSC Chemistry Examination Results</h1>
-        <p><strong>Submission Code:</strong> {}</p>
-        <p><strong>Submitted:</strong> {}</p>
-        <p><strong>Graded:</strong> {}</p>
-    </div>
-
-    <div class="score-summary">
-        <h2>Overall Performance</h2>
-        <p class="score">Total Score: {:.1}/{:.1} ({:.1}%)</p>
-        <p><strong>Time Taken:</strong> {:.1} minutes</p>
-        <p><strong>AI Grading Provider:</strong> {}</p>
-    </div>
-
-    <div class="overall-feedback">
-        <h2>Overall Feedback</h2>
-        <p>{}</p>
-    </div>
-
-    <div class="section-scores">
-        <h2>Section Performance</h2>
-        {}
-    </div>
-
-    <div class="question-by-question">
-        <h2>Question-by-Question Feedback</h2>
-        {}
-    </div>
-
-    <div class="footer">
-        <p>This report was generated automatically using AI-powered grading technology.</p>
-        <p>Generated on: {}</p>
-    </div>
-</body>
-</html>
-        "#,
-        submission.submission_code,
-        submission.submitted_at.format("%Y-%m-%d %H:%M:%S UTC"),
-        results.graded_at.format("%Y-%m-%d %H:%M:%S UTC"),
-        results.total_score,
-        results.max_score,
-        (results.total_score / results.max_score * 100.0),
-        submission.responses.time_taken_minutes,
-        results.ai_provider_used,
-        results.overall_feedback,
-        self.generate_section_scores_html(&results.section_scores),
-        self.generate_question_feedback_html(&results.question_feedback),
+    /// Reads the font files once; they are shared by every report rendered.
+    pub fn new(config: &Config, branding: Branding) -> Self {
+        Self {
+            fonts: Arc::new(FontData::load(&config.pdf_font_path, &config.pdf_bold_font_path)),
+            branding,
+        }
+    }
+
+    pub async fn generate_results_pdf(&self, submission: &Submission, exam: &ExamDefinition) -> Result<Vec<u8>> {
+        let service = self.clone();
+        let submission = submission.clone();
+        let exam = exam.clone();
+
+        // Layout is CPU-bound, so keep it off the async workers
+        tokio::task::spawn_blocking(move || service.render_results_pdf(&submission, &exam))
+            .await
+            .context("PDF rendering task panicked")?
+    }
+
+    /// Renders a report synchronously, for callers already on a blocking thread.
+    pub fn render_results_pdf(&self, submission: &Submission, exam: &ExamDefinition) -> Result<Vec<u8>> {
+        render_results(submission, exam, &self.branding, &self.fonts)
+    }
+}
+
+/// Font files read from disk, or `None` to fall back to the built-in fonts.
+struct FontData {
+    regular: Option<Vec<u8>>,
+    bold: Option<Vec<u8>>,
+}
+
+impl FontData {
+    fn load(regular: &str, bold: &str) -> Self {
+        let read = |path: &str| match std::fs::read(Path::new(path)) {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                tracing::warn!("Could not read PDF font {}, using built-in font: {}", path, e);
+                None
+            }
+        };
+
+        Self {
+            regular: read(regular),
+            bold: read(bold),
+        }
+    }
+}
+
+fn render_results(submission: &Submission, exam: &ExamDefinition, branding: &Branding, fonts: &FontData) -> Result<Vec<u8>> {
+    let results = submission
+        .results
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("No grading results available"))?;
+
+    let title = format!("{} Results", exam.title);
+    let mut writer = PageWriter::new(&title, fonts)?;
+
+    // Header
+    if let Some(school) = &branding.school_name {
+        writer.heading(school, 12.0);
+    }
+    if let Some(header) = &branding.header_text {
+        writer.paragraph(header, BODY_SIZE, false, 0.0);
+    }
+    writer.heading(&title, 18.0);
+    writer.field("Submission Code", &submission.submission_code);
+    writer.field("Submitted", &submission.submitted_at.format("%Y-%m-%d %H:%M:%S UTC").to_string());
+    writer.field("Graded", &results.graded_at.format("%Y-%m-%d %H:%M:%S UTC").to_string());
+    writer.rule();
+
+    // Overall performance
+    writer.heading("Overall Performance", 14.0);
+    writer.field(
+        "Total Score",
+        &format!(
+            "{:.1}/{:.1} ({:.1}%)",
+            results.total_score,
+            results.max_score,
+            percentage(results.total_score, results.max_score)
+        ),
+    );
+    if let Some(band) = &results.band {
+        writer.field("HSC Band", &band.summary());
+    }
+    writer.field("Time Taken", &format!("{:.1} minutes", submission.responses.time_taken_minutes));
+    writer.field("AI Grading Provider", &results.ai_provider_used);
+    writer.space(4.0);
+
+    writer.heading("Overall Feedback", 14.0);
+    writer.paragraph(&results.overall_feedback, BODY_SIZE, false, 0.0);
+    writer.space(4.0);
+
+    // Section scores, in exam order
+    writer.heading("Section Performance", 14.0);
+    writer.table_header(&[("Section", 0.0), ("Score", 120.0), ("%", 150.0)]);
+    for section in &exam.sections {
+        if let Some(score) = results.section_scores.get(&section.name) {
+            writer.table_row(&[
+                (&section.name, 0.0),
+                (&format!("{:.1}/{:.1}", score.score, score.max_score), 120.0),
+                (&format!("{:.0}%", percentage(score.score, score.max_score)), 150.0),
+            ]);
+            writer.paragraph(&score.feedback, SMALL_SIZE, false, 4.0);
+        }
+    }
+
+    // Question feedback starts on a fresh page
+    writer.new_page();
+    writer.heading("Question-by-Question Feedback", 14.0);
+    let (multiple_choice, written): (Vec<_>, Vec<_>) = results
+        .feedback_in_exam_order(exam)
+        .into_iter()
+        .partition(|(_, feedback)| feedback.multiple_choice.is_some());
+    if !multiple_choice.is_empty() {
+        write_multiple_choice_table(&mut writer, &multiple_choice);
+    }
+    for (question, feedback) in written {
+        write_question_feedback(&mut writer, question, feedback);
+    }
+
+    let generated = format!(
+        "Generated automatically using AI-powered grading on {}",
         Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
-        );
-
-        Ok(html)
-    }
-
-    fn generate_section_scores_html(&self, section_scores: &HashMap<String, crate::models::SectionScore>) -> String {
-        section_scores
-            .iter()
-            .map(|(section, score)| {
-                format!(
-                    r#"
-                    <div class="section-score">
-                        <h3>{}</h3>
-                        <p class="score">Score: {:.1}/{:.1}</p>
-                        <p>{}</p>
-                    </div>
-                    "#,
-                    section, score.score, score.max_score, score.feedback
-                )
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
-    }
-
-    fn generate_question_feedback_html(&self, question_feedback: &HashMap<String, crate::models::QuestionFeedback>) -> String {
-        question_feedback
-            .iter()
-            .map(|(question, feedback)| {
-                let strengths_html = if !feedback.strengths.is_empty() {
-                    format!(
-                        "<div class='strengths'><strong>Strengths:</strong><ul>{}</ul></div>",
-                        feedback.strengths.iter().map(|s| format!("<li>{}</li>", s)).collect::<Vec<_>>().join("")
-                    )
-                } else {
-                    String::new()
-                };
-
-                let improvements_html = if !feedback.improvements.is_empty() {
-                    format!(
-                        "<div class='improvements'><strong>Areas for Improvement:</strong><ul>{}</ul></div>",
-                        feedback.improvements.iter().map(|i| format!("<li>{}</li>", i)).collect::<Vec<_>>().join("")
-                    )
-                } else {
-                    String::new()
-                };
-
-                let band_html = if let Some(band) = &feedback.band_estimate {
-                    format!("<p><strong>Band Estimate:</strong> {}</p>", band)
-                } else {
-                    String::new()
-                };
-
-                format!(
-                    r#"
-                    <div class="question-feedback">
-                        <h3>Question {}</h3>
-                        <p class="score">Score: {:.1}/{:.1}</p>
-                        {}
-                        <div class="feedback-content">
-                            <p>{}</p>
-                            {}
-                            {}
-                        </div>
-                    </div>
-                    "#,
-                    question,
-                    feedback.score,
-                    feedback.max_score,
-                    band_html,
-                    feedback.feedback,
-                    strengths_html,
-                    improvements_html
-                )
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
-    }
-
-    async fn html_to_pdf(&self, html: &str) -> Result<Vec<u8>> {
-        use std::process::Command;
-        use tokio::fs;
-
-        // Create temporary file for HTML
-        let temp_html = format!("/tmp/{}.html", Uuid::new_v4());
-        let temp_pdf = format!("/tmp/{}.pdf", Uuid::new_v4());
-
-        fs::write(&temp_html, html).await?;
-
-        // Use wkhtmltopdf to convert HTML to PDF
-        let output = Command::new("wkhtmltopdf")
-            .arg("--page-size")
-            .arg("A4")
-            .arg("--margin-top")
-            .arg("0.75in")
-            .arg("--margin-right")
-            .arg("0.75in")
-            .arg("--margin-bottom")
-            .arg("0.75in")
-            .arg("--margin-left")
-            .arg("0.75in")
-            .arg("--enable-local-file-access")
-            .arg(&temp_html)
-            .arg(&temp_pdf)
-            .output()?;
-
-        if !output.status.success() {
-            let error = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("PDF conversion failed: {}", error));
-        }
-
-        let pdf_bytes = fs::read(&temp_pdf).await?;
-
-        // Clean up temporary files
-        let _ = fs::remove_file(&temp_html).await;
-        let _ = fs::remove_file(&temp_pdf).await;
-
-        Ok(pdf_bytes)
+    );
+    let footer = match &branding.footer_text {
+        Some(text) => format!("{} | {}", text, generated),
+        None => generated,
+    };
+    writer.finish(&footer)
+}
+
+/// One row per multiple-choice question, with the explanation and syllabus
+/// dot point listed under each question the student got wrong.
+fn write_multiple_choice_table(writer: &mut PageWriter, questions: &[(&String, &QuestionFeedback)]) {
+    writer.heading("Multiple Choice", 12.0);
+    writer.table_header(&[("Question", 0.0), ("Your Answer", 50.0), ("Correct Answer", 90.0), ("Result", 140.0)]);
+    for (question, feedback) in questions {
+        let Some(mc) = &feedback.multiple_choice else {
+            continue;
+        };
+        writer.table_row(&[
+            (&feedback.label(question), 0.0),
+            (mc.student_answer.as_deref().unwrap_or("-"), 50.0),
+            (&mc.correct_answer, 90.0),
+            (if mc.correct { "Correct" } else { "Incorrect" }, 140.0),
+        ]);
+        if !mc.correct {
+            if let Some(explanation) = &mc.explanation {
+                writer.paragraph(explanation, SMALL_SIZE, false, 4.0);
+            }
+            if let Some(point) = &mc.syllabus_point {
+                writer.paragraph(&format!("Syllabus: {}", point), SMALL_SIZE, false, 4.0);
+            }
+        }
+    }
+    writer.space(4.0);
+}
+
+fn write_question_feedback(writer: &mut PageWriter, question: &str, feedback: &QuestionFeedback) {
+    // Keep the heading with at least the first few lines of its block
+    writer.ensure_space(30.0);
+    writer.space(3.0);
+    let block_start = writer.position();
+
+    writer.heading(&format!("Question {}", feedback.label(question)), 12.0);
+    writer.field("Score", &format!("{:.1}/{:.1}", feedback.score, feedback.max_score));
+
+    match &feedback.status {
+        QuestionStatus::Graded => {}
+        QuestionStatus::Failed { .. } => writer.paragraph("Not yet graded: awaiting review.", BODY_SIZE, true, 0.0),
+        QuestionStatus::NeedsHuman { reason } => {
+            writer.paragraph(&format!("Flagged for teacher review: {}", reason), BODY_SIZE, true, 0.0)
+        }
+    }
+    if let Some(band) = &feedback.band_estimate {
+        writer.field("Band Estimate", band);
+    }
+
+    writer.paragraph(&feedback.feedback, BODY_SIZE, false, 0.0);
+
+    if !feedback.criteria.is_empty() {
+        writer.paragraph("Marking Criteria:", BODY_SIZE, true, 0.0);
+        for criterion in &feedback.criteria {
+            let mut line = format!(
+                "{} ({} marks): {}",
+                if criterion.awarded { "Met" } else { "Not met" },
+                criterion.marks,
+                criterion.description
+            );
+            if let Some(comment) = &criterion.comment {
+                line.push_str(&format!(" - {}", comment));
+            }
+            writer.bullet(&line);
+        }
+    }
+
+    if !feedback.equation_checks.is_empty() {
+        writer.paragraph("Equation Checks:", BODY_SIZE, true, 0.0);
+        for check in &feedback.equation_checks {
+            writer.bullet(&format!("{} - {}", check.equation, check.summary()));
+        }
+    }
+
+    if let Some(check) = &feedback.numeric_check {
+        writer.paragraph("Final Answer Check:", BODY_SIZE, true, 0.0);
+        writer.bullet(&check.summary());
+    }
+
+    if let Some(check) = &feedback.isomer_check {
+        writer.paragraph("Isomer Check:", BODY_SIZE, true, 0.0);
+        for pair in &check.pairs {
+            writer.bullet(&pair.comment);
+        }
+        for issue in &check.issues {
+            writer.bullet(issue);
+        }
+    }
+
+    if !feedback.strengths.is_empty() {
+        writer.paragraph("Strengths:", BODY_SIZE, true, 0.0);
+        for strength in &feedback.strengths {
+            writer.bullet(strength);
+        }
+    }
+
+    if !feedback.improvements.is_empty() {
+        writer.paragraph("Areas for Improvement:", BODY_SIZE, true, 0.0);
+        for improvement in &feedback.improvements {
+            writer.bullet(improvement);
+        }
+    }
+
+    writer.side_bar(block_start);
+}
+
+fn percentage(score: f64, max_score: f64) -> f64 {
+    if max_score > 0.0 {
+        score / max_score * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// A top-to-bottom text cursor over A4 pages that starts a new page when the
+/// current one is full.
+struct PageWriter {
+    document: PdfDocumentReference,
+    layers: Vec<PdfLayerReference>,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    /// Distance of the cursor from the bottom of the page, in mm.
+    y: f32,
+}
+
+impl PageWriter {
+    fn new(title: &str, fonts: &FontData) -> Result<Self> {
+        let (document, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Content");
+
+        let regular = match &fonts.regular {
+            Some(bytes) => document.add_external_font(bytes.as_slice()),
+            None => document.add_builtin_font(BuiltinFont::Helvetica),
+        }
+        .map_err(|e| anyhow::anyhow!("Failed to load PDF font: {}", e))?;
+        let bold = match &fonts.bold {
+            Some(bytes) => document.add_external_font(bytes.as_slice()),
+            None => document.add_builtin_font(BuiltinFont::HelveticaBold),
+        }
+        .map_err(|e| anyhow::anyhow!("Failed to load PDF bold font: {}", e))?;
+
+        let layer = document.get_page(page).get_layer(layer);
+        Ok(Self {
+            document,
+            layers: vec![layer],
+            regular,
+            bold,
+            y: PAGE_HEIGHT - MARGIN,
+        })
+    }
+
+    fn layer(&self) -> &PdfLayerReference {
+        self.layers.last().expect("writer always has a page")
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self.document.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Content");
+        self.layers.push(self.document.get_page(page).get_layer(layer));
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    /// Starts a new page unless `height` mm remain above the footer.
+    fn ensure_space(&mut self, height: f32) {
+        if self.y - height < MARGIN + FOOTER_HEIGHT {
+            self.new_page();
+        }
+    }
+
+    fn space(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    fn line_height(size: f32) -> f32 {
+        size * PT_TO_MM * LINE_SPACING
+    }
+
+    fn text_line(&mut self, text: &str, size: f32, bold: bool, x: f32) {
+        let height = Self::line_height(size);
+        self.ensure_space(height);
+        self.y -= height;
+        let font = if bold { &self.bold } else { &self.regular };
+        self.layer().use_text(text, size, Mm(MARGIN + x), Mm(self.y), font);
+    }
+
+    fn heading(&mut self, text: &str, size: f32) {
+        self.space(1.5);
+        for line in wrap(text, size, CONTENT_WIDTH) {
+            self.text_line(&line, size, true, 0.0);
+        }
+        self.space(1.5);
+    }
+
+    fn paragraph(&mut self, text: &str, size: f32, bold: bool, indent: f32) {
+        for line in text.lines().flat_map(|line| wrap(line, size, CONTENT_WIDTH - indent)) {
+            self.text_line(&line, size, bold, indent);
+        }
+    }
+
+    fn bullet(&mut self, text: &str) {
+        for (i, line) in wrap(text, BODY_SIZE, CONTENT_WIDTH - 8.0).into_iter().enumerate() {
+            if i == 0 {
+                let height = Self::line_height(BODY_SIZE);
+                self.ensure_space(height);
+                self.layer()
+                    .use_text("-", BODY_SIZE, Mm(MARGIN + 4.0), Mm(self.y - height), &self.regular);
+            }
+            self.text_line(&line, BODY_SIZE, false, 8.0);
+        }
+    }
+
+    /// A bold label followed by its value on the same line.
+    fn field(&mut self, label: &str, value: &str) {
+        let height = Self::line_height(BODY_SIZE);
+        self.ensure_space(height);
+        let label = format!("{}:", label);
+        let offset = text_width(&label, BODY_SIZE) + 2.0;
+        self.layer()
+            .use_text(&label, BODY_SIZE, Mm(MARGIN), Mm(self.y - height), &self.bold);
+
+        let lines = wrap(value, BODY_SIZE, CONTENT_WIDTH - offset);
+        if lines.is_empty() {
+            self.y -= height;
+        }
+        for line in lines {
+            self.text_line(&line, BODY_SIZE, false, offset);
+        }
+    }
+
+    fn table_header(&mut self, columns: &[(&str, f32)]) {
+        self.table_cells(columns, true);
+        self.rule();
+    }
+
+    fn table_row(&mut self, columns: &[(&str, f32)]) {
+        self.table_cells(columns, false);
+    }
+
+    fn table_cells(&mut self, columns: &[(&str, f32)], bold: bool) {
+        let height = Self::line_height(BODY_SIZE);
+        self.ensure_space(height);
+        self.y -= height;
+        let font = if bold { &self.bold } else { &self.regular };
+        for (text, x) in columns {
+            self.layer().use_text(*text, BODY_SIZE, Mm(MARGIN + x), Mm(self.y), font);
+        }
+    }
+
+    fn rule(&mut self) {
+        self.space(2.0);
+        self.layer().set_outline_color(grey());
+        self.layer().set_outline_thickness(0.5);
+        self.layer().add_line(Line {
+            points: vec![
+                (Point::new(Mm(MARGIN), Mm(self.y)), false),
+                (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(self.y)), false),
+            ],
+            is_closed: false,
+        });
+        self.space(3.0);
+    }
+
+    /// The current page number and cursor height.
+    fn position(&self) -> (usize, f32) {
+        (self.layers.len(), self.y)
+    }
+
+    /// Draws an accent bar beside a block that began at `start`, on the part
+    /// of the block that is on the current page.
+    fn side_bar(&mut self, start: (usize, f32)) {
+        let (page, y) = start;
+        let top = if page == self.layers.len() { y } else { PAGE_HEIGHT - MARGIN };
+        self.layer().set_fill_color(Color::Rgb(Rgb::new(0.0, 0.48, 1.0, None)));
+        self.layer()
+            .add_rect(Rect::new(Mm(MARGIN - 4.0), Mm(self.y - 1.0), Mm(MARGIN - 3.0), Mm(top)));
+        self.layer().set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+    }
+
+    /// Adds a footer with page numbers to every page and serialises the document.
+    fn finish(self, footer: &str) -> Result<Vec<u8>> {
+        let pages = self.layers.len();
+        for (i, layer) in self.layers.iter().enumerate() {
+            layer.set_fill_color(grey());
+            layer.use_text(footer, SMALL_SIZE, Mm(MARGIN), Mm(MARGIN - 5.0), &self.regular);
+            let page_label = format!("Page {} of {}", i + 1, pages);
+            let x = PAGE_WIDTH - MARGIN - text_width(&page_label, SMALL_SIZE);
+            layer.use_text(page_label, SMALL_SIZE, Mm(x), Mm(MARGIN - 5.0), &self.regular);
+        }
+
+        self.document
+            .save_to_bytes()
+            .map_err(|e| anyhow::anyhow!("Failed to write PDF: {}", e))
+    }
+}
+
+fn grey() -> Color {
+    Color::Rgb(Rgb::new(0.4, 0.4, 0.4, None))
+}
+
+fn text_width(text: &str, size: f32) -> f32 {
+    text.chars().count() as f32 * size * PT_TO_MM * AVERAGE_GLYPH_WIDTH
+}
+
+/// Greedy word wrap to an approximate line width in mm. Words longer than a
+/// line are split.
+fn wrap(text: &str, size: f32, width: f32) -> Vec<String> {
+    let max_chars = ((width / (size * PT_TO_MM * AVERAGE_GLYPH_WIDTH)) as usize).max(1);
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        while word.len() > max_chars {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            lines.push(word.drain(..max_chars).collect());
+        }
+        let word: String = word.into_iter().collect();
+
+        if current.is_empty() {
+            current = word;
+        } else if current.chars().count() + 1 + word.chars().count() <= max_chars {
+            current.push(' ');
+            current.push_str(&word);
+        } else {
+            lines.push(std::mem::replace(&mut current, word));
+        }
+    }
+
+    if !current.is_empty() {
+        lines.push(current);
     }
+    lines
 }
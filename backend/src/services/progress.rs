@@ -0,0 +1,220 @@
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::sync::broadcast;
+
+use crate::models::{GradingStatus, QuestionFeedback, QuestionStatus};
+
+/// Events buffered per submission for subscribers that fall behind.
+const CHANNEL_CAPACITY: usize = 64;
+
+/// A step in grading one submission, streamed to clients watching its progress.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressEvent {
+    #[serde(flatten)]
+    pub kind: ProgressKind,
+    /// Questions finished so far, out of `total`.
+    pub completed: usize,
+    pub total: usize,
+    /// Human-readable summary, e.g. `q22 graded 4/5`.
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressKind {
+    Started,
+    QuestionGraded { question_id: String, score: f64, max_score: f64 },
+    QuestionFlagged { question_id: String, reason: String },
+    QuestionFailed { question_id: String },
+    /// A provider failed and the next one is being tried.
+    Retrying { question_id: String, provider: String },
+    /// The grading attempt failed and will be retried later.
+    AttemptFailed { error: String },
+    /// Grading has stopped; no further events follow.
+    Finished { status: GradingStatus },
+}
+
+impl ProgressEvent {
+    pub fn finished(status: &GradingStatus, completed: usize, total: usize) -> Self {
+        let message = match status {
+            GradingStatus::Completed => "Grading completed".to_string(),
+            GradingStatus::PartiallyCompleted => "Grading finished; some questions await review".to_string(),
+            GradingStatus::Failed { error } => format!("Grading failed: {}", error),
+            GradingStatus::Pending | GradingStatus::InProgress => "Grading stopped".to_string(),
+        };
+        Self {
+            kind: ProgressKind::Finished { status: status.clone() },
+            completed,
+            total,
+            message,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self.kind, ProgressKind::Finished { .. })
+    }
+}
+
+struct Channel {
+    sender: broadcast::Sender<ProgressEvent>,
+    /// Replayed to new subscribers so they start from the current state.
+    latest: Option<ProgressEvent>,
+}
+
+/// Fans grading progress out to every client watching a submission.
+///
+/// Channels are created on first use by either side and dropped once grading
+/// finishes, so nothing is kept for submissions nobody is grading or watching.
+#[derive(Default)]
+pub struct ProgressHub {
+    channels: Mutex<HashMap<String, Channel>>,
+}
+
+impl ProgressHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes to a submission's events, returning the latest one if
+    /// grading is under way.
+    pub fn subscribe(&self, submission_code: &str) -> (Option<ProgressEvent>, broadcast::Receiver<ProgressEvent>) {
+        let mut channels = self.channels.lock().unwrap();
+        // Forget channels whose subscribers have all gone and that no grading is using
+        channels.retain(|_, channel| channel.sender.receiver_count() > 0 || channel.latest.is_some());
+
+        let channel = channels
+            .entry(submission_code.to_string())
+            .or_insert_with(|| Channel {
+                sender: broadcast::channel(CHANNEL_CAPACITY).0,
+                latest: None,
+            });
+        (channel.latest.clone(), channel.sender.subscribe())
+    }
+
+    pub fn publish(&self, submission_code: &str, event: ProgressEvent) {
+        let mut channels = self.channels.lock().unwrap();
+        let finished = event.is_finished();
+
+        match channels.get_mut(submission_code) {
+            Some(channel) => {
+                // Sending only fails when nobody is listening
+                let _ = channel.sender.send(event.clone());
+                channel.latest = Some(event);
+            }
+            None if !finished => {
+                channels.insert(
+                    submission_code.to_string(),
+                    Channel {
+                        sender: broadcast::channel(CHANNEL_CAPACITY).0,
+                        latest: Some(event),
+                    },
+                );
+            }
+            None => {}
+        }
+
+        if finished {
+            channels.remove(submission_code);
+        }
+    }
+
+    /// A reporter for one grading run of a submission.
+    pub fn reporter(self: &Arc<Self>, submission_code: &str) -> ProgressReporter {
+        ProgressReporter {
+            hub: Arc::clone(self),
+            submission_code: submission_code.to_string(),
+            completed: AtomicUsize::new(0),
+            total: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Publishes the progress of one grading run, keeping count of finished questions.
+pub struct ProgressReporter {
+    hub: Arc<ProgressHub>,
+    submission_code: String,
+    completed: AtomicUsize,
+    total: AtomicUsize,
+}
+
+impl ProgressReporter {
+    fn publish(&self, kind: ProgressKind, message: String) {
+        self.hub.publish(
+            &self.submission_code,
+            ProgressEvent {
+                kind,
+                completed: self.completed.load(Ordering::Relaxed),
+                total: self.total.load(Ordering::Relaxed),
+                message,
+            },
+        );
+    }
+
+    pub fn started(&self, total: usize) {
+        self.total.store(total, Ordering::Relaxed);
+        self.completed.store(0, Ordering::Relaxed);
+        self.publish(ProgressKind::Started, format!("Grading {} questions", total));
+    }
+
+    /// Records a finished question, whatever its outcome.
+    pub fn question_done(&self, feedback: &QuestionFeedback) {
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        let question_id = feedback.question_id.clone();
+
+        match &feedback.status {
+            QuestionStatus::Graded => self.publish(
+                ProgressKind::QuestionGraded {
+                    question_id: question_id.clone(),
+                    score: feedback.score,
+                    max_score: feedback.max_score,
+                },
+                format!("{} graded {}/{}", question_id, feedback.score, feedback.max_score),
+            ),
+            QuestionStatus::NeedsHuman { reason } => self.publish(
+                ProgressKind::QuestionFlagged {
+                    question_id: question_id.clone(),
+                    reason: reason.clone(),
+                },
+                format!("{} flagged for teacher review", question_id),
+            ),
+            QuestionStatus::Failed { .. } => self.publish(
+                ProgressKind::QuestionFailed {
+                    question_id: question_id.clone(),
+                },
+                format!("{} could not be graded", question_id),
+            ),
+        }
+    }
+
+    pub fn retrying(&self, question_id: &str, failed_provider: &str, next_provider: &str) {
+        self.publish(
+            ProgressKind::Retrying {
+                question_id: question_id.to_string(),
+                provider: next_provider.to_string(),
+            },
+            format!("{} retrying with {} after {} failed", question_id, next_provider, failed_provider),
+        );
+    }
+
+    pub fn attempt_failed(&self, error: &str) {
+        self.publish(
+            ProgressKind::AttemptFailed { error: error.to_string() },
+            "Grading attempt failed; it will be retried".to_string(),
+        );
+    }
+
+    pub fn finished(&self, status: &GradingStatus) {
+        let event = ProgressEvent::finished(
+            status,
+            self.completed.load(Ordering::Relaxed),
+            self.total.load(Ordering::Relaxed),
+        );
+        self.hub.publish(&self.submission_code, event);
+    }
+}
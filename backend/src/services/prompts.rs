@@ -0,0 +1,204 @@
+use anyhow::{Context, Result};
+use minijinja::{Environment, UndefinedBehavior};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::{
+    chemistry::{
+        isomers::NamedPair, nomenclature::IsomerKind, EquationCheck, IsomerCheck, NumericCheck, ResponseChecks,
+    },
+    models::exam::{MarkingCriterion, QuestionDefinition},
+};
+
+const SYSTEM_TEMPLATE: &str = "system.txt";
+const USER_TEMPLATE: &str = "user.txt";
+
+/// A grading prompt rendered for one response, sent unchanged to every provider.
+#[derive(Debug, Clone)]
+pub struct RenderedPrompt {
+    pub system: String,
+    pub user: String,
+}
+
+/// One version of the grading prompt, loaded from `<prompts dir>/<version>/`.
+///
+/// A version is a directory holding `system.txt` and `user.txt` MiniJinja
+/// templates. Versions are never edited once used, so results can be traced
+/// to the exact wording they were graded with.
+pub struct PromptTemplates {
+    version: String,
+    templates: Environment<'static>,
+}
+
+#[derive(Serialize)]
+struct PromptContext<'a> {
+    subject: &'a str,
+    /// Subject guidelines, from the exam or `review-prompt.md`.
+    guidelines: &'a str,
+    question: QuestionContext<'a>,
+    /// The shared stem when the question is a part, e.g. q25 for q25a.
+    parent: Option<QuestionContext<'a>>,
+    criteria: Vec<CriterionContext<'a>>,
+    exemplars: &'a [String],
+    response: String,
+    /// Deterministic balance and state-symbol checks of the response's equations.
+    equation_checks: &'a [EquationCheck],
+    /// Value, unit and significant-figure check of the final answer, if expected.
+    numeric_check: Option<&'a NumericCheck>,
+    /// The isomer pairs named in the response, when they did not decide the mark alone.
+    isomer_check: Option<&'a IsomerCheck>,
+}
+
+#[derive(Serialize)]
+struct QuestionContext<'a> {
+    id: &'a str,
+    text: &'a str,
+    marks: String,
+}
+
+#[derive(Serialize)]
+struct CriterionContext<'a> {
+    id: String,
+    marks: String,
+    description: &'a str,
+}
+
+impl<'a> QuestionContext<'a> {
+    fn new(question: &'a QuestionDefinition) -> Self {
+        Self {
+            id: &question.id,
+            text: &question.text,
+            marks: question.marks.to_string(),
+        }
+    }
+}
+
+impl PromptTemplates {
+    /// Loads `version` from `prompts_path`, rendering it once with a sample
+    /// question so template mistakes are reported at startup.
+    pub fn load(prompts_path: impl AsRef<Path>, version: &str) -> Result<Self> {
+        let dir = prompts_path.as_ref().join(version);
+        // Prompts are plain text, so nothing is escaped, but unknown variables are errors
+        let mut templates = Environment::new();
+        templates.set_undefined_behavior(UndefinedBehavior::Strict);
+
+        for name in [SYSTEM_TEMPLATE, USER_TEMPLATE] {
+            let path = dir.join(name);
+            let source = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read prompt template {}", path.display()))?;
+            templates
+                .add_template_owned(name, source)
+                .with_context(|| format!("Invalid prompt template {}", path.display()))?;
+        }
+
+        let prompts = Self {
+            version: version.to_string(),
+            templates,
+        };
+
+        let sample = QuestionDefinition {
+            id: "q1".to_string(),
+            kind: Default::default(),
+            text: "Sample question".to_string(),
+            marks: 2.0,
+            answer: None,
+            explanation: None,
+            syllabus_point: None,
+            criteria: vec![MarkingCriterion {
+                marks: 2.0,
+                description: "Sample criterion".to_string(),
+            }],
+            exemplars: vec!["Sample exemplar".to_string()],
+            numeric_answer: None,
+            isomers: None,
+            short_answer: None,
+            parts: Vec::new(),
+        };
+        let sample_checks = ResponseChecks {
+            equations: vec![EquationCheck {
+                equation: "H2(g) + O2(g) → H2O(l)".to_string(),
+                atoms_balanced: false,
+                charge_balanced: true,
+                missing_state_symbols: Vec::new(),
+                issues: vec!["O: 2 on the left, 1 on the right".to_string()],
+            }],
+            numeric: Some(NumericCheck {
+                expected: "0.774 g to 3 significant figures".to_string(),
+                answer: Some("0.77 g".to_string()),
+                value_correct: true,
+                unit_correct: true,
+                significant_figures_correct: false,
+                issues: vec!["Given to 2 significant figures; 3 required".to_string()],
+            }),
+            isomers: Some(IsomerCheck {
+                pairs: vec![NamedPair {
+                    claimed: IsomerKind::Chain,
+                    first: "butanal".to_string(),
+                    second: "2-methylpropanal".to_string(),
+                    correct: true,
+                    comment: "butanal and 2-methylpropanal are chain isomers of C4H8O".to_string(),
+                }],
+                score: 1.0,
+                conclusive: false,
+                issues: vec!["butan-3-one is numbered from the wrong end".to_string()],
+            }),
+        };
+        prompts
+            .render(
+                "Chemistry",
+                "Sample guidelines",
+                &sample,
+                Some(&sample),
+                &Value::from("Sample response"),
+                &sample_checks,
+            )
+            .with_context(|| format!("Prompt templates in {} failed to render", dir.display()))?;
+
+        Ok(prompts)
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn render(
+        &self,
+        subject: &str,
+        guidelines: &str,
+        question: &QuestionDefinition,
+        parent: Option<&QuestionDefinition>,
+        response: &Value,
+        checks: &ResponseChecks,
+    ) -> Result<RenderedPrompt> {
+        let context = PromptContext {
+            subject,
+            guidelines,
+            question: QuestionContext::new(question),
+            parent: parent.map(QuestionContext::new),
+            criteria: question
+                .criteria
+                .iter()
+                .enumerate()
+                .map(|(i, criterion)| CriterionContext {
+                    id: format!("C{}", i + 1),
+                    marks: criterion.marks.to_string(),
+                    description: &criterion.description,
+                })
+                .collect(),
+            exemplars: &question.exemplars,
+            response: match response {
+                Value::String(text) => text.clone(),
+                other => serde_json::to_string_pretty(other).unwrap_or_default(),
+            },
+            equation_checks: &checks.equations,
+            numeric_check: checks.numeric.as_ref(),
+            isomer_check: checks.isomers.as_ref(),
+        };
+
+        Ok(RenderedPrompt {
+            system: self.templates.get_template(SYSTEM_TEMPLATE)?.render(&context)?,
+            user: self.templates.get_template(USER_TEMPLATE)?.render(&context)?,
+        })
+    }
+}
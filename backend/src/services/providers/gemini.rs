@@ -0,0 +1,130 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{GradingProvider, GradingRequest};
+use crate::config::Config;
+
+pub struct GeminiProvider {
+    client: Client,
+    api_key: String,
+    api_url: String,
+    model: String,
+    name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiRequest {
+    contents: Vec<GeminiContent>,
+    generation_config: GeminiGenerationConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiContent {
+    parts: Vec<GeminiPart>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiPart {
+    text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiGenerationConfig {
+    temperature: f32,
+    max_output_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_schema: Option<Value>,
+}
+
+impl GeminiProvider {
+    pub fn new(client: Client, config: &Config) -> Result<Self> {
+        if config.gemini_api_key.is_empty() {
+            anyhow::bail!("GEMINI_API_KEY must be set to use the gemini grading provider");
+        }
+
+        Ok(Self {
+            client,
+            api_key: config.gemini_api_key.clone(),
+            api_url: config.gemini_api_url.trim_end_matches('/').to_string(),
+            model: config.gemini_model.clone(),
+            name: format!("Gemini {}", config.gemini_model),
+        })
+    }
+}
+
+#[async_trait]
+impl GradingProvider for GeminiProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn complete(&self, request: &GradingRequest<'_>) -> Result<String> {
+        // Gemini has no separate system role on this endpoint, so the
+        // guidelines are sent ahead of the grading prompt.
+        let prompt = format!("{}\n\n{}", request.system_prompt, request.user_prompt);
+
+        let body = GeminiRequest {
+            contents: vec![GeminiContent {
+                parts: vec![GeminiPart { text: prompt }],
+            }],
+            generation_config: GeminiGenerationConfig {
+                temperature: 0.1,
+                max_output_tokens: 2000,
+                response_mime_type: request.response_schema.map(|_| "application/json".to_string()),
+                response_schema: request.response_schema.map(openapi_schema),
+            },
+        };
+
+        let response = self
+            .client
+            .post(format!("{}/models/{}:generateContent", self.api_url, self.model))
+            .query(&[("key", &self.api_key)])
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let gemini_response: Value = response.json().await?;
+        let content = gemini_response["candidates"][0]["content"]["parts"][0]["text"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Gemini response contained no text"))?;
+
+        Ok(content.to_string())
+    }
+}
+
+/// Gemini accepts an OpenAPI subset of JSON schema: no `additionalProperties`,
+/// and optional values marked `nullable` rather than typed as `[T, "null"]`.
+fn openapi_schema(schema: &Value) -> Value {
+    match schema {
+        Value::Object(fields) => {
+            let mut converted = serde_json::Map::new();
+            for (key, value) in fields {
+                match (key.as_str(), value) {
+                    ("additionalProperties", _) => {}
+                    ("type", Value::Array(types)) => {
+                        let non_null: Vec<&Value> = types.iter().filter(|t| t.as_str() != Some("null")).collect();
+                        if let [single] = non_null.as_slice() {
+                            converted.insert("type".to_string(), (*single).clone());
+                        }
+                        if non_null.len() < types.len() {
+                            converted.insert("nullable".to_string(), Value::Bool(true));
+                        }
+                    }
+                    _ => {
+                        converted.insert(key.clone(), openapi_schema(value));
+                    }
+                }
+            }
+            Value::Object(converted)
+        }
+        Value::Array(items) => Value::Array(items.iter().map(openapi_schema).collect()),
+        other => other.clone(),
+    }
+}
@@ -0,0 +1,59 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::{GradingProvider, GradingRequest};
+
+/// Key used in fixture files for questions without their own entry.
+const DEFAULT_FIXTURE_KEY: &str = "*";
+
+/// Offline provider that replays scripted responses keyed by question id.
+///
+/// Fixture files are JSON objects mapping question ids to either a string
+/// (returned verbatim, so malformed output can be scripted too) or an object
+/// (returned serialised). A `"*"` entry is used for any other question.
+pub struct MockProvider {
+    responses: HashMap<String, String>,
+}
+
+impl MockProvider {
+    pub fn new(responses: HashMap<String, String>) -> Self {
+        Self { responses }
+    }
+
+    pub fn from_fixture_file(path: &str) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read mock fixtures from {}", path))?;
+        let fixtures: HashMap<String, Value> = serde_json::from_str(&content)
+            .with_context(|| format!("Mock fixtures in {} must be a JSON object", path))?;
+
+        let responses = fixtures
+            .into_iter()
+            .map(|(question_id, value)| {
+                let response = match value {
+                    Value::String(text) => text,
+                    other => other.to_string(),
+                };
+                (question_id, response)
+            })
+            .collect();
+
+        Ok(Self::new(responses))
+    }
+}
+
+#[async_trait]
+impl GradingProvider for MockProvider {
+    fn name(&self) -> &str {
+        "Mock"
+    }
+
+    async fn complete(&self, request: &GradingRequest<'_>) -> Result<String> {
+        self.responses
+            .get(request.question_id)
+            .or_else(|| self.responses.get(DEFAULT_FIXTURE_KEY))
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No mock fixture for question {}", request.question_id))
+    }
+}
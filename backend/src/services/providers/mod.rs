@@ -0,0 +1,64 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::config::Config;
+
+pub mod gemini;
+pub mod mock;
+pub mod openai;
+
+pub use gemini::GeminiProvider;
+pub use mock::MockProvider;
+pub use openai::OpenAIProvider;
+
+/// A fully rendered grading prompt for a single question.
+#[derive(Debug, Clone)]
+pub struct GradingRequest<'a> {
+    pub question_id: &'a str,
+    pub system_prompt: &'a str,
+    pub user_prompt: &'a str,
+    /// JSON schema the reply must follow, for providers with a structured-output mode.
+    pub response_schema: Option<&'a Value>,
+}
+
+/// A backend capable of marking a single response.
+///
+/// Providers only deal with transport: they receive a rendered prompt and
+/// return the raw model output. Parsing and validation stay in `AIService`.
+#[async_trait]
+pub trait GradingProvider: Send + Sync {
+    /// Label recorded against results, e.g. `OpenAI o1-mini`.
+    fn name(&self) -> &str;
+
+    async fn complete(&self, request: &GradingRequest<'_>) -> Result<String>;
+}
+
+/// Builds the provider chain named in `GRADING_PROVIDERS`, in fallback order.
+pub fn from_config(config: &Config) -> Result<Vec<Arc<dyn GradingProvider>>> {
+    let client = Client::new();
+    let mut providers: Vec<Arc<dyn GradingProvider>> = Vec::new();
+
+    for name in &config.grading_providers {
+        let provider: Arc<dyn GradingProvider> = match name.as_str() {
+            "openai" => Arc::new(OpenAIProvider::new(client.clone(), config)?),
+            "gemini" => Arc::new(GeminiProvider::new(client.clone(), config)?),
+            "mock" => {
+                let path = config.mock_fixtures_path.as_deref().ok_or_else(|| {
+                    anyhow::anyhow!("MOCK_FIXTURES_PATH must be set to use the mock grading provider")
+                })?;
+                Arc::new(MockProvider::from_fixture_file(path)?)
+            }
+            other => anyhow::bail!("Unknown grading provider: {}", other),
+        };
+        providers.push(provider);
+    }
+
+    if providers.is_empty() {
+        anyhow::bail!("At least one grading provider must be configured");
+    }
+
+    Ok(providers)
+}
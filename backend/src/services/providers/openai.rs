@@ -0,0 +1,108 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::{GradingProvider, GradingRequest};
+use crate::{config::Config, services::grading_schema::SCHEMA_NAME};
+
+pub struct OpenAIProvider {
+    client: Client,
+    api_key: String,
+    api_url: String,
+    model: String,
+    name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAIRequest {
+    model: String,
+    messages: Vec<OpenAIMessage>,
+    max_completion_tokens: u32,
+    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAIMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAIResponse {
+    choices: Vec<OpenAIChoice>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAIChoice {
+    message: OpenAIMessage,
+}
+
+impl OpenAIProvider {
+    pub fn new(client: Client, config: &Config) -> Result<Self> {
+        if config.openai_api_key.is_empty() {
+            anyhow::bail!("OPENAI_API_KEY must be set to use the openai grading provider");
+        }
+
+        Ok(Self {
+            client,
+            api_key: config.openai_api_key.clone(),
+            api_url: config.openai_api_url.trim_end_matches('/').to_string(),
+            model: config.openai_model.clone(),
+            name: format!("OpenAI {}", config.openai_model),
+        })
+    }
+}
+
+#[async_trait]
+impl GradingProvider for OpenAIProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn complete(&self, request: &GradingRequest<'_>) -> Result<String> {
+        let body = OpenAIRequest {
+            model: self.model.clone(),
+            messages: vec![
+                OpenAIMessage {
+                    role: "system".to_string(),
+                    content: request.system_prompt.to_string(),
+                },
+                OpenAIMessage {
+                    role: "user".to_string(),
+                    content: request.user_prompt.to_string(),
+                },
+            ],
+            max_completion_tokens: 2000,
+            temperature: 0.1,
+            response_format: request.response_schema.map(|schema| {
+                json!({
+                    "type": "json_schema",
+                    "json_schema": { "name": SCHEMA_NAME, "strict": true, "schema": schema }
+                })
+            }),
+        };
+
+        let response = self
+            .client
+            .post(format!("{}/chat/completions", self.api_url))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let openai_response: OpenAIResponse = response.json().await?;
+        let choice = openai_response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("OpenAI returned no choices"))?;
+
+        Ok(choice.message.content)
+    }
+}
@@ -0,0 +1,206 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+use crate::{
+    config::Config,
+    models::{GradingJob, GradingScope, GradingStatus},
+    services::{database::DatabaseService, grading::GradingService},
+};
+
+/// How long an idle worker waits before polling for jobs whose backoff has elapsed.
+const IDLE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Upper bound on the delay between retries of a failing job.
+const MAX_RETRY_DELAY_SECONDS: u64 = 60 * 60;
+
+/// Persistent grading queue backed by the `grading_jobs` table.
+///
+/// Jobs are leased by a fixed pool of workers, so the number of submissions
+/// being graded at once never exceeds `GRADING_WORKERS`, however many arrive.
+pub struct GradingQueue {
+    database: Arc<DatabaseService>,
+    grading: GradingService,
+    config: Arc<Config>,
+    notify: Notify,
+}
+
+impl GradingQueue {
+    pub fn new(database: Arc<DatabaseService>, grading: GradingService, config: Arc<Config>) -> Self {
+        Self {
+            database,
+            grading,
+            config,
+            notify: Notify::new(),
+        }
+    }
+
+    /// Queues a submission for grading and wakes an idle worker. With
+    /// `bypass_cache`, every response is graded afresh by the model.
+    pub async fn enqueue(&self, submission_code: &str, scope: GradingScope, bypass_cache: bool) -> Result<()> {
+        if self
+            .database
+            .enqueue_grading_job(submission_code, scope, bypass_cache, self.config.grading_max_attempts)
+            .await?
+        {
+            self.notify.notify_one();
+        }
+        Ok(())
+    }
+
+    /// Re-queues submissions left ungraded by a previous run. Jobs whose worker
+    /// died mid-grade are reclaimed by the workers once their lease expires.
+    pub async fn recover(&self) -> Result<usize> {
+        let mut requeued = 0;
+        for code in self.database.ungraded_submission_codes().await? {
+            if self
+                .database
+                .enqueue_grading_job(&code, GradingScope::Full, false, self.config.grading_max_attempts)
+                .await?
+            {
+                requeued += 1;
+            }
+        }
+        Ok(requeued)
+    }
+
+    /// Spawns the worker pool.
+    pub fn start(self: &Arc<Self>) {
+        for worker_id in 0..self.config.grading_workers.max(1) {
+            let queue = Arc::clone(self);
+            tokio::spawn(async move { queue.run_worker(worker_id).await });
+        }
+    }
+
+    async fn run_worker(&self, worker_id: usize) {
+        loop {
+            match self.database.lease_next_grading_job(self.lease_until()).await {
+                Ok(Some(job)) => {
+                    tracing::info!(
+                        "Worker {} grading {} (attempt {}/{})",
+                        worker_id, job.submission_code, job.attempts, job.max_attempts
+                    );
+                    self.run_job(job).await;
+                }
+                Ok(None) => {
+                    self.fail_abandoned_jobs().await;
+                    tokio::select! {
+                        _ = self.notify.notified() => {}
+                        _ = tokio::time::sleep(IDLE_POLL_INTERVAL) => {}
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("Worker {} failed to lease a grading job: {}", worker_id, e);
+                    tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    /// Fails jobs whose lease ran out on their last attempt. They are never
+    /// reclaimed, so without this their submissions would stay in progress.
+    async fn fail_abandoned_jobs(&self) {
+        let error = "Grading stopped responding on every attempt";
+        let jobs = match self.database.fail_abandoned_grading_jobs(error).await {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                tracing::error!("Failed to sweep abandoned grading jobs: {}", e);
+                return;
+            }
+        };
+
+        for job in jobs {
+            tracing::error!(
+                "Grading failed for {} after {} attempts: the worker stopped responding",
+                job.submission_code, job.attempts
+            );
+            let status = GradingStatus::Failed { error: error.to_string() };
+            if let Err(e) = self.database.update_grading_status(&job.submission_code, status.clone()).await {
+                tracing::error!("Failed to record outcome of grading job {}: {}", job.job_id, e);
+            }
+            self.grading.progress(&job.submission_code).finished(&status);
+        }
+    }
+
+    /// Grades the job's submission, renewing the lease a few times per lease
+    /// period so a slow grading is not reclaimed and graded twice.
+    async fn run_job(&self, job: GradingJob) {
+        let grading = self
+            .grading
+            .process_submission(&job.submission_code, job.scope, job.bypass_cache);
+        tokio::pin!(grading);
+
+        let renew_every = std::time::Duration::from_secs((self.config.grading_lease_seconds / 3).max(1));
+        let mut heartbeat = tokio::time::interval_at(tokio::time::Instant::now() + renew_every, renew_every);
+        let graded = loop {
+            tokio::select! {
+                graded = &mut grading => break graded,
+                _ = heartbeat.tick() => self.renew_lease(&job).await,
+            }
+        };
+        let outcome = match graded {
+            Ok(()) => self.database.complete_grading_job(&job.job_id).await,
+            Err(e) => self.handle_failure(&job, &e.to_string()).await,
+        };
+
+        if let Err(e) = outcome {
+            tracing::error!("Failed to record outcome of grading job {}: {}", job.job_id, e);
+        }
+    }
+
+    async fn renew_lease(&self, job: &GradingJob) {
+        match self.database.renew_grading_lease(&job.job_id, job.attempts, self.lease_until()).await {
+            Ok(true) => {}
+            Ok(false) => tracing::warn!(
+                "Grading job {} for {} lost its lease and may be graded again",
+                job.job_id, job.submission_code
+            ),
+            Err(e) => tracing::error!("Failed to renew the lease of grading job {}: {}", job.job_id, e),
+        }
+    }
+
+    fn lease_until(&self) -> DateTime<Utc> {
+        Utc::now() + Duration::seconds(self.config.grading_lease_seconds as i64)
+    }
+
+    async fn handle_failure(&self, job: &GradingJob, error: &str) -> Result<()> {
+        if job.attempts >= job.max_attempts {
+            tracing::error!(
+                "Grading failed for {} after {} attempts: {}",
+                job.submission_code, job.attempts, error
+            );
+            self.database.fail_grading_job(&job.job_id, error).await?;
+            let status = GradingStatus::Failed { error: error.to_string() };
+            self.database
+                .update_grading_status(&job.submission_code, status.clone())
+                .await?;
+            self.grading.progress(&job.submission_code).finished(&status);
+        } else {
+            let delay = self.retry_delay(job.attempts);
+            tracing::warn!(
+                "Grading attempt {} for {} failed, retrying in {}s: {}",
+                job.attempts, job.submission_code, delay.num_seconds(), error
+            );
+            self.database
+                .retry_grading_job(&job.job_id, error, Utc::now() + delay)
+                .await?;
+            self.database
+                .update_grading_status(&job.submission_code, GradingStatus::Pending)
+                .await?;
+            self.grading.progress(&job.submission_code).attempt_failed(error);
+        }
+        Ok(())
+    }
+
+    /// Exponential backoff: base, 2×base, 4×base, ... capped at one hour.
+    fn retry_delay(&self, attempts: u32) -> Duration {
+        let exponent = attempts.saturating_sub(1).min(16);
+        let seconds = self
+            .config
+            .grading_retry_base_seconds
+            .saturating_mul(1 << exponent)
+            .min(MAX_RETRY_DELAY_SECONDS);
+        Duration::seconds(seconds as i64)
+    }
+}
@@ -0,0 +1,272 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use minijinja::Environment;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::{
+    models::{ExamDefinition, GradingResults, QuestionFeedback, QuestionStatus, Submission},
+    services::analytics::{ItemAnalysis, ItemStatistics},
+};
+
+const REPORT_TEMPLATE: &str = "report.html";
+const ITEM_ANALYSIS_TEMPLATE: &str = "item_analysis.html";
+/// Built-in templates, each replaceable by a file of the same name in the templates directory.
+const DEFAULT_TEMPLATES: &[(&str, &str)] = &[
+    (REPORT_TEMPLATE, include_str!("../../templates/report.html")),
+    (ITEM_ANALYSIS_TEMPLATE, include_str!("../../templates/item_analysis.html")),
+];
+const BRANDING_FILE: &str = "branding.toml";
+
+/// School branding shown on HTML and PDF reports, read from `branding.toml`
+/// in the templates directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Branding {
+    pub school_name: Option<String>,
+    /// Logo shown in the HTML report header, e.g. `/assets/logo.png`.
+    pub logo_url: Option<String>,
+    pub header_text: Option<String>,
+    pub footer_text: Option<String>,
+    /// Hex colour such as `#005a9c`, used for accents in the HTML report.
+    pub accent_color: Option<String>,
+}
+
+impl Branding {
+    /// Reads `branding.toml` from `dir`, or returns empty branding if there is none.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let path = dir.as_ref().join(BRANDING_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read branding {}", path.display()))?;
+        let branding: Self = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse branding {}", path.display()))?;
+
+        // The colour is written into a stylesheet, where HTML escaping does not help
+        if let Some(color) = &branding.accent_color {
+            let is_hex = color.len() == 7
+                && color.starts_with('#')
+                && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+            if !is_hex {
+                anyhow::bail!("accent_color '{}' in {} must be a hex colour like #005a9c", color, path.display());
+            }
+        }
+
+        Ok(branding)
+    }
+}
+
+/// Renders results and teacher reports as HTML pages for viewing in the browser.
+///
+/// Pages come from `.html` templates, which autoescape every value so model
+/// output cannot inject markup. A template of the same name in the templates
+/// directory replaces the built-in one.
+pub struct ReportService {
+    templates: Environment<'static>,
+    branding: Branding,
+}
+
+#[derive(Serialize)]
+struct ReportContext<'a> {
+    title: String,
+    branding: &'a Branding,
+    submission_code: &'a str,
+    submitted_at: String,
+    graded_at: String,
+    total_score: String,
+    band: Option<String>,
+    time_taken: String,
+    ai_provider_used: &'a str,
+    overall_feedback: &'a str,
+    sections: Vec<SectionContext<'a>>,
+    questions: Vec<QuestionContext<'a>>,
+    generated_at: String,
+}
+
+#[derive(Serialize)]
+struct SectionContext<'a> {
+    name: &'a str,
+    score: String,
+    feedback: &'a str,
+}
+
+#[derive(Serialize)]
+struct QuestionContext<'a> {
+    label: String,
+    score: String,
+    status_note: Option<String>,
+    feedback: &'a QuestionFeedback,
+}
+
+impl ReportService {
+    pub fn new(templates_path: impl AsRef<Path>, branding: Branding) -> Result<Self> {
+        // Autoescaping is on for `.html` templates
+        let mut templates = Environment::new();
+
+        for (name, default) in DEFAULT_TEMPLATES {
+            let override_path = templates_path.as_ref().join(name);
+            let source = if override_path.exists() {
+                std::fs::read_to_string(&override_path)
+                    .with_context(|| format!("Failed to read report template {}", override_path.display()))?
+            } else {
+                default.to_string()
+            };
+
+            templates
+                .add_template_owned(*name, source)
+                .with_context(|| format!("Invalid report template {}", override_path.display()))?;
+        }
+
+        Ok(Self { templates, branding })
+    }
+
+    pub fn generate_html_report(&self, submission: &Submission, exam: &ExamDefinition, results: &GradingResults) -> Result<String> {
+        let context = ReportContext {
+            title: format!("{} Results", exam.title),
+            branding: &self.branding,
+            submission_code: &submission.submission_code,
+            submitted_at: submission.submitted_at.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            graded_at: results.graded_at.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            total_score: format!(
+                "{:.1}/{:.1} ({:.1}%)",
+                results.total_score,
+                results.max_score,
+                if results.max_score > 0.0 { results.total_score / results.max_score * 100.0 } else { 0.0 }
+            ),
+            band: results.band.as_ref().map(|band| band.summary()),
+            time_taken: format!("{:.1} minutes", submission.responses.time_taken_minutes),
+            ai_provider_used: &results.ai_provider_used,
+            overall_feedback: &results.overall_feedback,
+            // Render sections in the order the exam defines them
+            sections: exam
+                .sections
+                .iter()
+                .filter_map(|section| {
+                    results.section_scores.get(&section.name).map(|score| SectionContext {
+                        name: &section.name,
+                        score: format!("{:.1}/{:.1}", score.score, score.max_score),
+                        feedback: &score.feedback,
+                    })
+                })
+                .collect(),
+            questions: results
+                .feedback_in_exam_order(exam)
+                .into_iter()
+                .map(|(key, feedback)| QuestionContext {
+                    label: feedback.label(key),
+                    score: format!("{:.1}/{:.1}", feedback.score, feedback.max_score),
+                    status_note: match &feedback.status {
+                        QuestionStatus::Graded => None,
+                        QuestionStatus::Failed { .. } => Some("Not yet graded: awaiting review.".to_string()),
+                        QuestionStatus::NeedsHuman { reason } => Some(format!("Flagged for teacher review: {}", reason)),
+                    },
+                    feedback,
+                })
+                .collect(),
+            generated_at: Utc::now().format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        };
+
+        self.templates
+            .get_template(REPORT_TEMPLATE)?
+            .render(context)
+            .context("Failed to render report template")
+    }
+
+    /// Item statistics for a cohort, flagging items teachers should review.
+    pub fn generate_item_analysis_report(
+        &self,
+        exam: &ExamDefinition,
+        analysis: &ItemAnalysis,
+        class: Option<&str>,
+    ) -> Result<String> {
+        let context = ItemAnalysisContext {
+            title: format!("{} Item Analysis", exam.title),
+            branding: &self.branding,
+            class,
+            cohort_size: analysis.cohort_size,
+            group_size: analysis.group_size,
+            items: analysis
+                .items
+                .iter()
+                .map(|item| ItemContext {
+                    question_id: &item.question_id,
+                    correct_answer: &item.correct_answer,
+                    facility: format!("{:.0}%", item.facility),
+                    discrimination: item.discrimination.map_or("-".to_string(), |d| format!("{:.2}", d)),
+                    point_biserial: item.point_biserial.map_or("-".to_string(), |r| format!("{:.2}", r)),
+                    options: item
+                        .options
+                        .iter()
+                        .map(|o| OptionContext {
+                            option: &o.option,
+                            summary: format!("{} ({:.0}%)", o.count, o.percentage),
+                            correct: o.correct,
+                        })
+                        .collect(),
+                    omitted: item.omitted,
+                    review_note: review_note(item),
+                })
+                .collect(),
+            generated_at: Utc::now().format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        };
+
+        self.templates
+            .get_template(ITEM_ANALYSIS_TEMPLATE)?
+            .render(context)
+            .context("Failed to render item analysis template")
+    }
+}
+
+#[derive(Serialize)]
+struct ItemAnalysisContext<'a> {
+    title: String,
+    branding: &'a Branding,
+    class: Option<&'a str>,
+    cohort_size: usize,
+    group_size: usize,
+    items: Vec<ItemContext<'a>>,
+    generated_at: String,
+}
+
+#[derive(Serialize)]
+struct ItemContext<'a> {
+    question_id: &'a str,
+    correct_answer: &'a str,
+    facility: String,
+    discrimination: String,
+    point_biserial: String,
+    options: Vec<OptionContext<'a>>,
+    omitted: usize,
+    review_note: Option<String>,
+}
+
+#[derive(Serialize)]
+struct OptionContext<'a> {
+    option: &'a str,
+    summary: String,
+    correct: bool,
+}
+
+/// Flags items using common classroom rules of thumb.
+fn review_note(item: &ItemStatistics) -> Option<String> {
+    let popular_distractor = item
+        .options
+        .iter()
+        .filter(|o| !o.correct)
+        .max_by_key(|o| o.count)
+        .filter(|o| o.percentage > item.facility);
+
+    if item.discrimination.is_some_and(|d| d < 0.0) {
+        Some("Negative discrimination: weaker students did better; check the key.".to_string())
+    } else if let Some(distractor) = popular_distractor {
+        Some(format!("Option {} was chosen more often than the correct answer.", distractor.option))
+    } else if item.discrimination.is_some_and(|d| d < 0.2) {
+        Some("Low discrimination: the item barely separates stronger and weaker students.".to_string())
+    } else if item.facility < 30.0 {
+        Some("Difficult item: fewer than 30% answered correctly.".to_string())
+    } else {
+        None
+    }
+}
@@ -0,0 +1,213 @@
+use serde_json::Value;
+
+use crate::{
+    chemistry::nomenclature::HYPHENS,
+    models::{
+        exam::{AnswerRule, ExpectedAnswer, QuestionDefinition, ShortAnswerKey},
+        CriterionResult, QuestionFeedback,
+    },
+};
+
+/// Marks a short-answer response against its accepted answers. Returns
+/// `None` when any answered field matches neither an accepted nor a rejected
+/// answer, so the response must be graded by the model instead.
+pub fn mark(question: &QuestionDefinition, response: &Value) -> Option<QuestionFeedback> {
+    let key = question.short_answer.as_ref()?;
+    let mut criteria = Vec::new();
+
+    for rule in &key.rules {
+        let given: Vec<&str> = rule.fields.iter().map(|field| field_answer(response, field)).collect();
+        let awarded = if given.iter().any(|answer| answer.trim().is_empty()) {
+            false
+        } else {
+            let given: Vec<String> = given.iter().map(|answer| key.normalise(answer)).collect();
+            if key.matches(rule, &rule.accepted, &given) {
+                true
+            } else if key.matches(rule, &rule.rejected, &given) {
+                false
+            } else {
+                tracing::info!(
+                    "No answer rule matches {:?} for {} on {}; grading with the model",
+                    given,
+                    rule.fields.join(", "),
+                    question.id
+                );
+                return None;
+            }
+        };
+
+        criteria.push(CriterionResult {
+            description: rule.description.clone().unwrap_or_else(|| rule.fields.join(", ")),
+            marks: rule.marks,
+            awarded,
+            comment: Some(if given.iter().all(|answer| answer.trim().is_empty()) {
+                "Not answered".to_string()
+            } else {
+                given
+                    .iter()
+                    .map(|answer| answer.trim().trim_end_matches(['.', ';', ',']))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }),
+        });
+    }
+
+    let score = criteria.iter().filter(|c| c.awarded).fold(0.0, |score, c| score + c.marks);
+    Some(QuestionFeedback {
+        question_id: question.id.clone(),
+        score,
+        max_score: question.marks,
+        feedback: format!("Marked against the accepted answers: {} of {} marks.", score, question.marks),
+        criteria,
+        ..Default::default()
+    })
+}
+
+/// The student's answer to one field; a plain-text response answers every field.
+fn field_answer<'a>(response: &'a Value, field: &str) -> &'a str {
+    match response {
+        Value::String(text) => text,
+        other => other.get(field).and_then(Value::as_str).unwrap_or_default(),
+    }
+}
+
+impl ShortAnswerKey {
+    /// Applies the key's rules for case, hyphens and spacing, then replaces
+    /// a synonym with the first answer in its group.
+    fn normalise(&self, answer: &str) -> String {
+        let normalised = self.normalise_text(answer);
+        self.synonyms
+            .iter()
+            .find(|group| group.iter().any(|synonym| self.normalise_text(synonym) == normalised))
+            .and_then(|group| group.first())
+            .map(|preferred| self.normalise_text(preferred))
+            .unwrap_or(normalised)
+    }
+
+    fn normalise_text(&self, answer: &str) -> String {
+        let mut text: String = answer
+            .trim()
+            .trim_end_matches(['.', ';', ','])
+            .chars()
+            .map(|c| match c {
+                c if HYPHENS.contains(&c) => '-',
+                '₀'..='₉' => char::from_digit(c as u32 - '₀' as u32, 10).unwrap_or(c),
+                c => c,
+            })
+            .collect();
+        if !self.case_sensitive {
+            text = text.to_lowercase();
+        }
+        if !self.hyphens_significant {
+            text.retain(|c| c != '-');
+        }
+        if self.spacing_significant {
+            text.split_whitespace().collect::<Vec<_>>().join(" ")
+        } else {
+            text.split_whitespace().collect()
+        }
+    }
+
+    fn matches(&self, rule: &AnswerRule, expected: &[ExpectedAnswer], given: &[String]) -> bool {
+        let comparable = |answers: Vec<String>| {
+            let mut answers = answers;
+            if rule.any_order {
+                answers.sort();
+            }
+            answers
+        };
+        let given = comparable(given.to_vec());
+        expected
+            .iter()
+            .any(|answer| comparable(answer.values().iter().map(|value| self.normalise(value)).collect()) == given)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ExamDefinition;
+    use serde_json::json;
+
+    fn q21() -> QuestionDefinition {
+        let exam: ExamDefinition = toml::from_str(include_str!("../../../exams/hsc-chemistry.toml")).unwrap();
+        let q21 = exam.questions().find(|q| q.id == "q21").unwrap().clone();
+        q21
+    }
+
+    /// A one-field question whose key is `key`.
+    fn question(key: &str) -> QuestionDefinition {
+        toml::from_str(&format!(
+            "id = \"q1\"\nmarks = 1\n\n[short_answer]\n{}\n\n[[short_answer.rules]]\n\
+             fields = [\"answer\"]\nmarks = 1\naccepted = [\"sodium chloride\", \"2-methylpropan-1-ol\"]",
+            key
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn marks_pairs_in_any_order_with_synonyms() {
+        let feedback = mark(
+            &q21(),
+            &json!({
+                "functional_1": "butanal",
+                "functional_2": "2-Butanone.",
+                "chain_1": "methylpropanal",
+                "chain_2": "butanal",
+            }),
+        )
+        .unwrap();
+
+        assert_eq!(feedback.score, 2.0);
+        assert!(feedback.criteria.iter().all(|c| c.awarded));
+        assert_eq!(feedback.criteria[0].description, "Functional group isomers");
+        assert_eq!(feedback.criteria[0].comment.as_deref(), Some("butanal, 2-Butanone"));
+    }
+
+    #[test]
+    fn gives_no_marks_for_rejected_and_blank_answers() {
+        let feedback = mark(
+            &q21(),
+            &json!({
+                "functional_1": "butanal",
+                "functional_2": "2-methylpropanal",
+                "chain_1": "",
+            }),
+        )
+        .unwrap();
+
+        assert_eq!(feedback.score, 0.0);
+        assert!(!feedback.criteria[0].awarded);
+        assert_eq!(feedback.criteria[1].comment.as_deref(), Some("Not answered"));
+    }
+
+    #[test]
+    fn escalates_unknown_answers_to_the_model() {
+        let response = json!({
+            "functional_1": "butan-2-one",
+            "functional_2": "butan-2-ol",
+            "chain_1": "butanal",
+            "chain_2": "2-methylpropanal",
+        });
+        assert!(mark(&q21(), &response).is_none());
+        assert!(mark(&question(""), &json!("potassium chloride")).is_none());
+    }
+
+    #[test]
+    fn ignores_case_hyphens_and_spacing_by_default() {
+        let question = question("");
+        for answer in ["Sodium  Chloride", "sodiumchloride", "2–methylpropan–1–ol", "2-methyl propan-1-ol;"] {
+            assert_eq!(mark(&question, &json!(answer)).unwrap().score, 1.0, "{}", answer);
+        }
+    }
+
+    #[test]
+    fn keeps_significant_case_hyphens_and_spacing() {
+        let question = question("case_sensitive = true\nhyphens_significant = true\nspacing_significant = true");
+        assert_eq!(mark(&question, &json!("sodium  chloride")).unwrap().score, 1.0);
+        assert_eq!(mark(&question, &json!("2‐methylpropan‐1‐ol")).unwrap().score, 1.0);
+        for answer in ["Sodium chloride", "sodiumchloride", "2-methylpropan1ol"] {
+            assert!(mark(&question, &json!(answer)).is_none(), "{}", answer);
+        }
+    }
+}